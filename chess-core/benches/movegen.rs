@@ -0,0 +1,47 @@
+// compares the move generator with the former brute force one,
+// run with `cargo bench -p chess-core`
+use std::time::{Duration, Instant};
+
+use chess_core::{Cmd, Game, START_FEN};
+
+const POSITIONS: [(&str, &str); 3] = [
+    ("initial", START_FEN),
+    (
+        "kiwipete",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    ),
+    ("endgame", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1"),
+];
+
+// the mean time of one call, repeated for at least half a second
+fn measure(game: &Game, generate: fn(&Game) -> Vec<Cmd>) -> (Duration, usize) {
+    let start = Instant::now();
+    let mut runs = 0;
+    let mut count = 0;
+    while runs == 0 || start.elapsed() < Duration::from_millis(500) {
+        count = generate(game).len();
+        runs += 1;
+    }
+    (start.elapsed() / runs, count)
+}
+
+fn main() {
+    println!(
+        "{:<10} {:>6} {:>14} {:>14} {:>9}",
+        "position", "moves", "valid_cmds", "brute force", "speedup"
+    );
+    for (name, fen) in POSITIONS {
+        let game = Game::from_fen(fen).unwrap();
+        let (fast, count) = measure(&game, Game::valid_cmds);
+        let (slow, brute_count) = measure(&game, Game::valid_cmds_brute_force);
+        assert_eq!(count, brute_count, "{}", name);
+        println!(
+            "{:<10} {:>6} {:>14?} {:>14?} {:>8.1}x",
+            name,
+            count,
+            fast,
+            slow,
+            slow.as_secs_f64() / fast.as_secs_f64()
+        );
+    }
+}
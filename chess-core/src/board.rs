@@ -0,0 +1,276 @@
+use crate::zobrist::piece_key;
+use crate::{Camp, ChessError, King, Piece, PieceKind, Vec2};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// the pieces on the board: a kind and a camp per square, and the same
+// placement as bitboards where bit x + 8 * y stands for the square (x,y),
+// serialized as the map of the pieces keyed by Vec2::to_string()
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(from = "BoardView", into = "BoardView")]
+pub struct ChessBoard {
+    squares: [Option<(PieceKind, Camp)>; 64],
+    // one bitboard per camp, then one per kind
+    camps: [u64; 2],
+    kinds: [u64; 6],
+    // the squares of the pawns, kings and rooks which have moved
+    moved: u64,
+    // the Zobrist keys of the pieces xored together
+    hash: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BoardView {
+    board: HashMap<String, Piece>,
+}
+
+impl From<BoardView> for ChessBoard {
+    fn from(view: BoardView) -> Self {
+        let mut board = ChessBoard::new();
+        for p in view.board.into_values() {
+            if p.get_base().pos.is_on_board() {
+                board.insert_piece(p);
+            }
+        }
+        board
+    }
+}
+
+impl From<ChessBoard> for BoardView {
+    fn from(board: ChessBoard) -> Self {
+        let board = board
+            .pieces()
+            .into_iter()
+            .map(|p| (p.get_base().pos.to_string(), p))
+            .collect();
+        BoardView { board }
+    }
+}
+
+fn index(pos: Vec2) -> usize {
+    (pos.x + 8 * pos.y) as usize
+}
+
+fn camp_index(camp: Camp) -> usize {
+    match camp {
+        Camp::White => 0,
+        Camp::Black => 1,
+    }
+}
+
+// the squares of the set bits, a1 first
+fn squares_of(mut bits: u64) -> Vec<Vec2> {
+    let mut squares = Vec::new();
+    while bits != 0 {
+        let i = bits.trailing_zeros() as i32;
+        squares.push(Vec2::new(i % 8, i / 8));
+        bits &= bits - 1;
+    }
+    squares
+}
+
+fn has_moved_flag(piece: &Piece) -> Option<bool> {
+    match piece {
+        Piece::Pawn(p) => Some(p.moved),
+        Piece::King(p) => Some(p.moved),
+        Piece::Rook(p) => Some(p.moved),
+        _ => None,
+    }
+}
+
+impl ChessBoard {
+    pub fn new() -> Self {
+        ChessBoard {
+            squares: [None; 64],
+            camps: [0; 2],
+            kinds: [0; 6],
+            moved: 0,
+            hash: 0,
+        }
+    }
+
+    pub fn get_piece(&self, pos: Vec2) -> Option<Piece> {
+        if !pos.is_on_board() {
+            return None;
+        }
+        let (kind, camp) = self.squares[index(pos)]?;
+        let mut piece = Piece::new(kind, pos.x, pos.y, camp);
+        let moved = self.moved & (1 << index(pos)) != 0;
+        match &mut piece {
+            Piece::Pawn(p) => p.moved = moved,
+            Piece::King(p) => p.moved = moved,
+            Piece::Rook(p) => p.moved = moved,
+            _ => {}
+        }
+        Some(piece)
+    }
+
+    // the piece takes the place of the one on its square, if any
+    pub(crate) fn insert_piece(&mut self, p: Piece) {
+        let pos = p.get_base().pos;
+        self.clear(pos);
+
+        let i = index(pos);
+        let (kind, camp) = (p.kind(), p.get_base().camp);
+        self.squares[i] = Some((kind, camp));
+        self.camps[camp_index(camp)] |= 1 << i;
+        self.kinds[kind as usize] |= 1 << i;
+        self.hash ^= piece_key(kind, camp, i);
+        if has_moved_flag(&p) == Some(true) {
+            self.moved |= 1 << i;
+        }
+    }
+
+    // moves the piece on from, capturing whatever stands on to
+    pub fn move_piece(&mut self, from: Vec2, to: Vec2) -> Result<(), ChessError> {
+        if !to.is_on_board() {
+            return Err(ChessError::OffBoard);
+        }
+        let mut p = self.remove_piece(from)?;
+        p.change_pos(to);
+        self.insert_piece(p);
+        Ok(())
+    }
+
+    pub(crate) fn remove_piece(&mut self, pos: Vec2) -> Result<Piece, ChessError> {
+        let p = self.get_piece(pos).ok_or(ChessError::NoPieceAtSource)?;
+        self.clear(pos);
+        Ok(p)
+    }
+
+    fn clear(&mut self, pos: Vec2) {
+        let i = index(pos);
+        if let Some((kind, camp)) = self.squares[i].take() {
+            self.camps[camp_index(camp)] &= !(1 << i);
+            self.kinds[kind as usize] &= !(1 << i);
+            self.hash ^= piece_key(kind, camp, i);
+        }
+        self.moved &= !(1 << i);
+    }
+
+    // fen sets the flag of the kings and rooks from the castling rights
+    pub(crate) fn set_moved(&mut self, pos: Vec2, moved: bool) {
+        if moved {
+            self.moved |= 1 << index(pos);
+        } else {
+            self.moved &= !(1 << index(pos));
+        }
+    }
+
+    // the squares of the pieces of a kind and a camp
+    pub fn bitboard(&self, kind: PieceKind, camp: Camp) -> u64 {
+        self.kinds[kind as usize] & self.camps[camp_index(camp)]
+    }
+
+    // the squares of every piece of the camp
+    pub fn camp_bitboard(&self, camp: Camp) -> u64 {
+        self.camps[camp_index(camp)]
+    }
+
+    pub fn occupied(&self) -> u64 {
+        self.camps[0] | self.camps[1]
+    }
+
+    // the hash of the placement only, Game::hash() adds the rest of the position
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn piece_count(&self) -> usize {
+        self.occupied().count_ones() as usize
+    }
+
+    // every piece, a1 first then along the ranks
+    pub fn pieces(&self) -> Vec<Piece> {
+        self.pieces_of(self.occupied())
+    }
+
+    fn pieces_of(&self, bits: u64) -> Vec<Piece> {
+        squares_of(bits)
+            .into_iter()
+            .filter_map(|pos| self.get_piece(pos))
+            .collect()
+    }
+
+    pub(crate) fn get_king_of_camp(&self, camp: Camp) -> King {
+        match self.get_king_of_camp_opt(camp) {
+            Some(k) => k,
+            None => panic!("king not found"),
+        }
+    }
+
+    pub(crate) fn get_king_of_camp_opt(&self, camp: Camp) -> Option<King> {
+        let pos = *squares_of(self.bitboard(PieceKind::King, camp)).first()?;
+        match self.get_piece(pos) {
+            Some(Piece::King(k)) => Some(k),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn get_piece_of_camp(&self, camp: Camp) -> Vec<Piece> {
+        self.pieces_of(self.camp_bitboard(camp))
+    }
+}
+
+impl Default for ChessBoard {
+    fn default() -> Self {
+        ChessBoard::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Camp, ChessBoard, Game, Piece, PieceKind, Vec2};
+
+    #[test]
+    fn bitboards_follow_the_pieces() {
+        let game = Game::new();
+        let board = &game.board;
+        assert_eq!(board.piece_count(), 32);
+        assert_eq!(board.bitboard(PieceKind::Pawn, Camp::White), 0xff00);
+        assert_eq!(board.bitboard(PieceKind::King, Camp::Black), 1 << 60);
+        assert_eq!(board.camp_bitboard(Camp::Black), 0xffff << 48);
+
+        let mut board = board.clone();
+        // Nb1xb7: the knight replaces the pawn in every bitboard
+        board.move_piece(Vec2::new(1, 0), Vec2::new(1, 6)).unwrap();
+        assert_eq!(board.piece_count(), 31);
+        assert_eq!(
+            board.bitboard(PieceKind::Knight, Camp::White),
+            1 << 6 | 1 << 49
+        );
+        assert_eq!(board.bitboard(PieceKind::Pawn, Camp::Black), 0xfd << 48);
+        assert!(matches!(
+            board.get_piece(Vec2::new(1, 6)),
+            Some(Piece::Knight(k)) if k.base.is_white()
+        ));
+        assert!(board.get_piece(Vec2::new(1, 0)).is_none());
+        assert!(board.move_piece(Vec2::new(1, 0), Vec2::new(2, 2)).is_err());
+        assert!(board.move_piece(Vec2::new(1, 6), Vec2::new(1, 8)).is_err());
+    }
+
+    #[test]
+    fn moved_flags_are_kept() {
+        let mut board = Game::new().board;
+        board.move_piece(Vec2::new(7, 0), Vec2::new(7, 2)).unwrap();
+        assert!(matches!(board.get_piece(Vec2::new(7, 2)), Some(Piece::Rook(r)) if r.moved));
+        assert!(matches!(board.get_piece(Vec2::new(0, 0)), Some(Piece::Rook(r)) if !r.moved));
+    }
+
+    #[test]
+    fn json_keeps_the_piece_map() {
+        let board = Game::new().board;
+        let json = serde_json::to_value(&board).unwrap();
+        let map = json["board"].as_object().unwrap();
+        assert_eq!(map.len(), 32);
+        assert_eq!(map["(4,0)"]["King"]["base"]["name"], "king");
+
+        let back: ChessBoard = serde_json::from_value(json).unwrap();
+        assert_eq!(back.occupied(), board.occupied());
+        for kind in [PieceKind::Pawn, PieceKind::Queen, PieceKind::Rook] {
+            for camp in [Camp::White, Camp::Black] {
+                assert_eq!(back.bitboard(kind, camp), board.bitboard(kind, camp));
+            }
+        }
+    }
+}
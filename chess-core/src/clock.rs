@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Camp;
+
+const DAY: u64 = 24 * 60 * 60 * 1000;
+
+// how much time the players have, in milliseconds
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TimeControl {
+    // the increment is added after each move
+    Fischer { base: u64, increment: u64 },
+    // the time a move took is given back, up to the delay
+    Bronstein { base: u64, delay: u64 },
+    // days for each move, the clock is full again after every one
+    Correspondence { days: u64 },
+}
+
+impl TimeControl {
+    pub fn base(&self) -> u64 {
+        match *self {
+            TimeControl::Fischer { base, .. } | TimeControl::Bronstein { base, .. } => base,
+            TimeControl::Correspondence { days } => days * DAY,
+        }
+    }
+}
+
+// the clocks of both camps; times are milliseconds of a clock the caller
+// chooses, now only has to grow. Nothing runs until white's first move, which
+// starts the clock of black
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Clock {
+    control: TimeControl,
+    // what each camp had left when its clock last stopped
+    white: u64,
+    black: u64,
+    // the camp whose clock runs, and since when
+    running: Option<(Camp, u64)>,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Self {
+        Clock {
+            control,
+            white: control.base(),
+            black: control.base(),
+            running: None,
+        }
+    }
+
+    pub fn control(&self) -> TimeControl {
+        self.control
+    }
+
+    pub fn running(&self) -> Option<Camp> {
+        self.running.map(|(camp, _)| camp)
+    }
+
+    fn left_mut(&mut self, camp: Camp) -> &mut u64 {
+        match camp {
+            Camp::White => &mut self.white,
+            Camp::Black => &mut self.black,
+        }
+    }
+
+    pub fn remaining(&self, camp: Camp, now: u64) -> u64 {
+        let left = match camp {
+            Camp::White => self.white,
+            Camp::Black => self.black,
+        };
+        match self.running {
+            Some((c, since)) if c == camp => left.saturating_sub(now.saturating_sub(since)),
+            _ => left,
+        }
+    }
+
+    // the camp whose time is up
+    pub fn flagged(&self, now: u64) -> Option<Camp> {
+        self.running().filter(|c| self.remaining(*c, now) == 0)
+    }
+
+    // the camp has moved: its clock stops, gets what the control gives back,
+    // and the clock of the other camp starts
+    pub fn press(&mut self, camp: Camp, now: u64) {
+        let used = match self.running {
+            Some((c, since)) if c == camp => now.saturating_sub(since),
+            _ => 0,
+        };
+        let left = self.remaining(camp, now);
+        *self.left_mut(camp) = match self.control {
+            TimeControl::Fischer { increment, .. } => left + increment,
+            TimeControl::Bronstein { delay, .. } => left + used.min(delay),
+            TimeControl::Correspondence { .. } => self.control.base(),
+        };
+        self.running = Some((camp.opposite(), now));
+    }
+
+    // the game is over, the time left stays as it is
+    pub fn stop(&mut self, now: u64) {
+        if let Some(camp) = self.running() {
+            *self.left_mut(camp) = self.remaining(camp, now);
+        }
+        self.running = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, TimeControl, DAY};
+    use crate::Camp;
+
+    #[test]
+    fn controls_give_time_back() {
+        let mut fischer = Clock::new(TimeControl::Fischer {
+            base: 60_000,
+            increment: 2_000,
+        });
+        // white's first move is free
+        fischer.press(Camp::White, 5_000);
+        assert_eq!(fischer.remaining(Camp::White, 5_000), 62_000);
+        assert_eq!(fischer.remaining(Camp::Black, 15_000), 50_000);
+        fischer.press(Camp::Black, 15_000);
+        assert_eq!(fischer.remaining(Camp::Black, 20_000), 52_000);
+        assert_eq!(fischer.running(), Some(Camp::White));
+
+        let mut bronstein = Clock::new(TimeControl::Bronstein {
+            base: 60_000,
+            delay: 3_000,
+        });
+        bronstein.press(Camp::White, 0);
+        bronstein.press(Camp::Black, 2_000);
+        assert_eq!(bronstein.remaining(Camp::Black, 2_000), 60_000);
+        bronstein.press(Camp::White, 12_000);
+        assert_eq!(bronstein.remaining(Camp::White, 12_000), 53_000);
+
+        let mut correspondence = Clock::new(TimeControl::Correspondence { days: 2 });
+        correspondence.press(Camp::White, 0);
+        assert_eq!(correspondence.remaining(Camp::Black, DAY), DAY);
+        correspondence.press(Camp::Black, DAY);
+        assert_eq!(correspondence.remaining(Camp::Black, DAY), 2 * DAY);
+    }
+
+    #[test]
+    fn a_flag_falls_only_while_running() {
+        let mut clock = Clock::new(TimeControl::Fischer {
+            base: 1_000,
+            increment: 0,
+        });
+        assert_eq!(clock.flagged(10_000), None);
+        clock.press(Camp::White, 10_000);
+        assert_eq!(clock.flagged(10_999), None);
+        assert_eq!(clock.flagged(11_000), Some(Camp::Black));
+
+        clock.stop(10_500);
+        assert_eq!(clock.remaining(Camp::Black, 20_000), 500);
+        assert_eq!(clock.flagged(20_000), None);
+    }
+}
@@ -0,0 +1,66 @@
+use crate::PieceKind;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// why a cmd or a notated move was refused
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ChessError {
+    GameOver,
+    NotYourTurn,
+    NoPieceAtSource,
+    OffBoard,
+    CaptureOwnPiece,
+    // the piece can not move that way, whatever stands on the board
+    IllegalPieceMove { piece: PieceKind },
+    // another piece stands on the way
+    Blocked,
+    WouldLeaveKingInCheck,
+    CastlingOutOfCheck,
+    CastlingThroughCheck,
+    // a pawn reaching the last rank must name the piece it becomes
+    PromotionRequired,
+    IllegalPromotion,
+    NoDrawToClaim,
+    NothingToUndo,
+    NothingToRedo,
+    // errors of the SAN and UCI notations
+    NotAMove,
+    UnparsableMove,
+    IllegalMove,
+    AmbiguousMove,
+}
+
+impl fmt::Display for ChessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChessError::GameOver => write!(f, "the game is over"),
+            ChessError::NotYourTurn => write!(f, "not your turn"),
+            ChessError::NoPieceAtSource => write!(f, "there is no piece on that square"),
+            ChessError::OffBoard => write!(f, "the square is off the board"),
+            ChessError::CaptureOwnPiece => write!(f, "can not capture a piece of your own camp"),
+            ChessError::IllegalPieceMove { piece } => {
+                write!(f, "{:?} can not move like that", piece)
+            }
+            ChessError::Blocked => write!(f, "blocked by another piece"),
+            ChessError::WouldLeaveKingInCheck => write!(f, "your king would be in check"),
+            ChessError::CastlingOutOfCheck => write!(f, "king can not castle out of check"),
+            ChessError::CastlingThroughCheck => {
+                write!(f, "king can not castle through an attacked square")
+            }
+            ChessError::PromotionRequired => write!(f, "choose the piece the pawn promotes to"),
+            ChessError::IllegalPromotion => write!(
+                f,
+                "only a pawn reaching the last rank can promote, to a queen, rook, bishop or knight"
+            ),
+            ChessError::NoDrawToClaim => write!(f, "no draw can be claimed"),
+            ChessError::NothingToUndo => write!(f, "there is no move to take back"),
+            ChessError::NothingToRedo => write!(f, "there is no move to play again"),
+            ChessError::NotAMove => write!(f, "not a move"),
+            ChessError::UnparsableMove => write!(f, "can not parse the move"),
+            ChessError::IllegalMove => write!(f, "illegal move"),
+            ChessError::AmbiguousMove => write!(f, "ambiguous move"),
+        }
+    }
+}
+
+impl std::error::Error for ChessError {}
@@ -0,0 +1,368 @@
+use crate::{Bishop, Camp, ChessBoard, Game, King, Knight, Pawn, Piece, Queen, Rook, Stage, Vec2};
+use std::fmt;
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    // a FEN has 6 fields, the two counters may be left out
+    FieldCount(usize),
+    Placement(String),
+    SideToMove(String),
+    Castling(String),
+    EnPassant(String),
+    Counter(String),
+    // each camp must have exactly one king
+    KingCount(Camp),
+    // the side not to move is in check, its king could be taken
+    OpponentInCheck,
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::FieldCount(n) => write!(f, "expected 4 or 6 fields, found {}", n),
+            FenError::Placement(s) => write!(f, "bad piece placement: {}", s),
+            FenError::SideToMove(s) => write!(f, "bad side to move: {}", s),
+            FenError::Castling(s) => write!(f, "bad castling rights: {}", s),
+            FenError::EnPassant(s) => write!(f, "bad en passant square: {}", s),
+            FenError::Counter(s) => write!(f, "bad move counter: {}", s),
+            FenError::KingCount(camp) => write!(f, "{:?} must have exactly one king", camp),
+            FenError::OpponentInCheck => write!(f, "the side not to move is in check"),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+impl Game {
+    pub fn from_fen(fen: &str) -> Result<Game, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() != 4 && fields.len() != 6 {
+            return Err(FenError::FieldCount(fields.len()));
+        }
+
+        let board = parse_placement(fields[0])?;
+
+        let turn = match fields[1] {
+            "w" => Camp::White,
+            "b" => Camp::Black,
+            s => return Err(FenError::SideToMove(s.to_string())),
+        };
+
+        let mut game = Game {
+            stage: Stage {
+                turn,
+                result: None,
+                en_passant: None,
+                halfmove_clock: 0,
+                fullmove_number: 1,
+            },
+            board,
+            positions: Vec::new(),
+            start_fen: String::new(),
+            history: Vec::new(),
+            undone: Vec::new(),
+        };
+
+        for camp in [Camp::White, Camp::Black] {
+            let kings = game
+                .board
+                .get_piece_of_camp(camp)
+                .iter()
+                .filter(|p| matches!(p, Piece::King(_)))
+                .count();
+            if kings != 1 {
+                return Err(FenError::KingCount(camp));
+            }
+        }
+        let king = game.board.get_king_of_camp(turn.opposite()).base.pos;
+        if game.board.is_attacked(king, turn) {
+            return Err(FenError::OpponentInCheck);
+        }
+
+        game.set_castling_rights(fields[2])?;
+        game.stage.en_passant = parse_en_passant(fields[3], turn)?;
+
+        if fields.len() == 6 {
+            game.stage.halfmove_clock = parse_counter(fields[4])?;
+            game.stage.fullmove_number = parse_counter(fields[5])?;
+            if game.stage.fullmove_number == 0 {
+                return Err(FenError::Counter(fields[5].to_string()));
+            }
+        }
+
+        game.positions.push(game.hash());
+        game.start_fen = game.to_fen();
+        game.update_result();
+        Ok(game)
+    }
+
+    pub fn to_fen(&self) -> String {
+        format!(
+            "{} {} {}",
+            self.position_key(),
+            self.stage.halfmove_clock,
+            self.stage.fullmove_number
+        )
+    }
+
+    // the first four fields of the FEN: pieces, turn, castling rights and en passant
+    fn position_key(&self) -> String {
+        let mut key = String::new();
+        for y in (0..8).rev() {
+            let mut empty = 0;
+            for x in 0..8 {
+                match self.board.get_piece(Vec2::new(x, y)) {
+                    Some(p) => {
+                        if empty > 0 {
+                            key.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        key.push(p.symbol());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                key.push_str(&empty.to_string());
+            }
+            if y > 0 {
+                key.push('/');
+            }
+        }
+
+        match self.stage.turn {
+            Camp::White => key.push_str(" w "),
+            Camp::Black => key.push_str(" b "),
+        }
+
+        let rights = self.castling_rights();
+        if rights.is_empty() {
+            key.push('-');
+        } else {
+            key.push_str(&rights);
+        }
+
+        match self.stage.en_passant {
+            Some(pos) => key.push_str(&format!(" {}", pos.to_square())),
+            None => key.push_str(" -"),
+        }
+        key
+    }
+
+    // every piece is parsed as unmoved, only the kings and rooks
+    // keeping a castling right stay unmoved
+    fn set_castling_rights(&mut self, rights: &str) -> Result<(), FenError> {
+        if rights != "-" && (rights.is_empty() || !rights.chars().all(|c| "KQkq".contains(c))) {
+            return Err(FenError::Castling(rights.to_string()));
+        }
+
+        let mut unmoved = Vec::new();
+        for camp in [Camp::White, Camp::Black] {
+            let back_rank = if camp == Camp::White { 0 } else { 7 };
+            let king_pos = self.board.get_king_of_camp(camp).base.pos;
+
+            for (rook_x, c) in [(7, 'K'), (0, 'Q')] {
+                let c = if camp == Camp::White {
+                    c
+                } else {
+                    c.to_ascii_lowercase()
+                };
+                if !rights.contains(c) {
+                    continue;
+                }
+
+                let rook_pos = Vec2::new(rook_x, back_rank);
+                let rook_ok = matches!(self.board.get_piece(rook_pos),
+                    Some(Piece::Rook(r)) if r.base.is_camp(camp));
+                if king_pos != Vec2::new(4, back_rank) || !rook_ok {
+                    return Err(FenError::Castling(rights.to_string()));
+                }
+                unmoved.push(rook_pos);
+                unmoved.push(king_pos);
+            }
+        }
+
+        for p in self.board.pieces() {
+            if let Piece::King(_) | Piece::Rook(_) = p {
+                let pos = p.get_base().pos;
+                self.board.set_moved(pos, !unmoved.contains(&pos));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_placement(placement: &str) -> Result<ChessBoard, FenError> {
+    let err = || FenError::Placement(placement.to_string());
+
+    let ranks: Vec<&str> = placement.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(err());
+    }
+
+    let mut board = ChessBoard::new();
+    for (i, rank) in ranks.iter().enumerate() {
+        let y = 7 - i as i32;
+        let mut x = 0;
+        for c in rank.chars() {
+            if let Some(d) = c.to_digit(10) {
+                if d == 0 || d > 8 {
+                    return Err(err());
+                }
+                x += d as i32;
+                continue;
+            }
+            if x > 7 {
+                return Err(err());
+            }
+            board.insert_piece(piece_from_symbol(c, x, y).ok_or_else(err)?);
+            x += 1;
+        }
+        if x != 8 {
+            return Err(err());
+        }
+    }
+    Ok(board)
+}
+
+fn piece_from_symbol(c: char, x: i32, y: i32) -> Option<Piece> {
+    let camp = if c.is_ascii_uppercase() {
+        Camp::White
+    } else {
+        Camp::Black
+    };
+    let piece = match c.to_ascii_lowercase() {
+        'p' => {
+            if y == 0 || y == 7 {
+                return None;
+            }
+            let mut pawn = Pawn::new(x, y, camp);
+            pawn.moved = (camp == Camp::White && y != 1) || (camp == Camp::Black && y != 6);
+            Piece::Pawn(pawn)
+        }
+        'n' => Piece::Knight(Knight::new(x, y, camp)),
+        'b' => Piece::Bishop(Bishop::new(x, y, camp)),
+        'r' => Piece::Rook(Rook::new(x, y, camp)),
+        'q' => Piece::Queen(Queen::new(x, y, camp)),
+        'k' => Piece::King(King::new(x, y, camp)),
+        _ => return None,
+    };
+    Some(piece)
+}
+
+fn parse_en_passant(s: &str, turn: Camp) -> Result<Option<Vec2>, FenError> {
+    if s == "-" {
+        return Ok(None);
+    }
+    let pos = Vec2::from_square(s).ok_or_else(|| FenError::EnPassant(s.to_string()))?;
+    // the square is behind a pawn of the side which just moved
+    let expected_y = if turn == Camp::White { 5 } else { 2 };
+    if pos.y != expected_y {
+        return Err(FenError::EnPassant(s.to_string()));
+    }
+    Ok(Some(pos))
+}
+
+fn parse_counter(s: &str) -> Result<u32, FenError> {
+    s.parse::<u32>()
+        .map_err(|_| FenError::Counter(s.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cmd, FenError, Game, MoveCmd, Vec2, START_FEN};
+
+    #[test]
+    fn start_position() {
+        assert_eq!(Game::new().to_fen(), START_FEN);
+    }
+
+    #[test]
+    fn round_trip() {
+        let fens = [
+            START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2",
+            "4k3/8/8/8/8/8/8/R3K3 b Q - 12 40",
+        ];
+        for fen in fens {
+            assert_eq!(Game::from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn counters_are_optional() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K2R w K -").unwrap();
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+    }
+
+    #[test]
+    fn moves_update_the_fen() {
+        let mut game = Game::new();
+        let mv = |x1, y1, x2, y2| Cmd::Move(MoveCmd::new(Vec2::new(x1, y1), Vec2::new(x2, y2)));
+        game.exec_cmd(&mv(4, 1, 4, 3)).unwrap();
+        assert_eq!(
+            game.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+        game.exec_cmd(&mv(6, 7, 5, 5)).unwrap();
+        game.exec_cmd(&mv(7, 1, 7, 2)).unwrap();
+        game.exec_cmd(&mv(7, 7, 6, 7)).unwrap();
+        assert_eq!(
+            game.to_fen(),
+            "rnbqkbr1/pppppppp/5n2/8/4P3/7P/PPPP1PP1/RNBQKBNR w KQq - 1 3"
+        );
+    }
+
+    #[test]
+    fn castling_rights_follow_moved_flags() {
+        let game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1").unwrap();
+        assert_eq!(game.castling_rights(), "Kq");
+
+        let game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1").unwrap();
+        assert_eq!(game.castling_rights(), "");
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            (
+                "8/8/8/8/8/8/8 w - - 0 1",
+                FenError::Placement("8/8/8/8/8/8/8".to_string()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 x - - 0 1",
+                FenError::SideToMove("x".to_string()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w K - 0 1",
+                FenError::Castling("K".to_string()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/3K3R w K - 0 1",
+                FenError::Castling("K".to_string()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - e4 0 1",
+                FenError::EnPassant("e4".to_string()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - x 1",
+                FenError::Counter("x".to_string()),
+            ),
+            (
+                "8/8/8/8/8/8/8/4K3 w - - 0 1",
+                FenError::KingCount(crate::Camp::Black),
+            ),
+            ("4k3/8/8/8/8/8/8/4K3 w - 0 1", FenError::FieldCount(5)),
+            ("4k3/4Q3/8/8/8/8/8/4K3 w - - 0 1", FenError::OpponentInCheck),
+        ];
+        for (fen, err) in cases {
+            assert_eq!(Game::from_fen(fen).unwrap_err(), err);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{abs, ChessError, Cmd, Game, MoveCmd, Piece, PieceKind, Stage, Vec2};
+use serde::{Deserialize, Serialize};
+
+// an accepted cmd and what is needed to take it back
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MoveRecord {
+    pub cmd: Cmd,
+    // the moving piece as it stood before the move, None for a resignation or a draw claim
+    pub piece: Option<Piece>,
+    // the captured piece on its own square, which is not the target of an en passant capture
+    pub captured: Option<Piece>,
+    stage: Stage,
+}
+
+impl MoveRecord {
+    // the stage before the cmd
+    pub fn stage_before(&self) -> &Stage {
+        &self.stage
+    }
+}
+
+impl Game {
+    // every accepted cmd, in order, the undone ones excepted
+    pub fn history(&self) -> &[MoveRecord] {
+        &self.history
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    // takes back the last cmd, which redo() plays again until another cmd is executed
+    pub fn undo(&mut self) -> Result<Cmd, ChessError> {
+        let record = self.history.pop().ok_or(ChessError::NothingToUndo)?;
+
+        if let (Cmd::Move(m), Some(piece)) = (&record.cmd, &record.piece) {
+            self.take_back(m, piece, &record.captured);
+            self.positions.pop();
+        }
+        self.stage = record.stage;
+        self.undone.push(record.cmd.clone());
+        Ok(record.cmd)
+    }
+
+    pub fn redo(&mut self) -> Result<Cmd, ChessError> {
+        let cmd = self.undone.pop().ok_or(ChessError::NothingToRedo)?;
+        let undone = std::mem::take(&mut self.undone);
+        let res = self.exec_cmd(&cmd);
+        self.undone = undone;
+        if let Err(e) = res {
+            self.undone.push(cmd);
+            return Err(e);
+        }
+        Ok(cmd)
+    }
+
+    // what undo() needs to know about the cmd, taken before it is executed
+    pub(crate) fn record_of(&self, cmd: &Cmd) -> MoveRecord {
+        let mut record = MoveRecord {
+            cmd: cmd.clone(),
+            piece: None,
+            captured: None,
+            stage: self.stage.clone(),
+        };
+        if let Cmd::Move(m) = cmd {
+            record.piece = self.board.get_piece(m.from);
+            record.captured = self.board.get_piece(m.to);
+            if let Some(Piece::Pawn(_)) = record.piece {
+                if record.captured.is_none() && m.from.x != m.to.x {
+                    record.captured = self.board.get_piece(Vec2::new(m.to.x, m.from.y));
+                }
+            }
+        }
+        record
+    }
+
+    // a new cmd forgets the undone ones
+    pub(crate) fn push_history(&mut self, record: MoveRecord) {
+        self.history.push(record);
+        self.undone.clear();
+    }
+
+    fn take_back(&mut self, m: &MoveCmd, piece: &Piece, captured: &Option<Piece>) {
+        // the promoted piece leaves with the pawn
+        let _ = self.board.remove_piece(m.to);
+        self.board.insert_piece(piece.clone());
+        if let Some(p) = captured {
+            self.board.insert_piece(p.clone());
+        }
+
+        if let Piece::King(_) = piece {
+            if abs(m.to.x - m.from.x) == 2 {
+                let (rook_x, castled_x) = if m.to.x == 2 { (0, 3) } else { (7, 5) };
+                let castled = Vec2::new(castled_x, m.from.y);
+                if let Ok(rook) = self.board.remove_piece(castled) {
+                    let camp = rook.get_base().camp;
+                    self.board
+                        .insert_piece(Piece::new(PieceKind::Rook, rook_x, m.from.y, camp));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::play_san;
+    use crate::{Camp, ChessError, Cmd, Game, MoveCmd, Vec2, START_FEN};
+
+    #[test]
+    fn undo_restores_every_position() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        // en passant, both castlings and a promotion with capture
+        let moves = [
+            "a4", "bxa3", "O-O", "hxg2", "Qxf6", "gxf1=Q+", "Kxf1", "O-O-O",
+        ];
+
+        let mut fens = vec![game.to_fen()];
+        for m in moves {
+            play_san(&mut game, &[m]);
+            fens.push(game.to_fen());
+        }
+        assert_eq!(game.history().len(), moves.len());
+
+        for i in (0..moves.len()).rev() {
+            game.undo().unwrap();
+            assert_eq!(game.to_fen(), fens[i]);
+        }
+        assert_eq!(game.undo(), Err(ChessError::NothingToUndo));
+        assert_eq!(game.valid_cmds().len(), 48);
+
+        for fen in &fens[1..] {
+            game.redo().unwrap();
+            assert_eq!(game.to_fen(), *fen);
+        }
+        assert_eq!(game.redo(), Err(ChessError::NothingToRedo));
+    }
+
+    #[test]
+    fn history_keeps_captures_and_prior_stage() {
+        let mut game = Game::new();
+        play_san(&mut game, &["e4", "d5", "exd5"]);
+
+        let last = game.history().last().unwrap();
+        let exd5 = MoveCmd::new(
+            Vec2::from_square("e4").unwrap(),
+            Vec2::from_square("d5").unwrap(),
+        );
+        assert_eq!(last.cmd, Cmd::Move(exd5));
+        assert_eq!(last.piece.as_ref().unwrap().symbol(), 'P');
+        assert_eq!(last.captured.as_ref().unwrap().symbol(), 'p');
+        assert_eq!(last.stage_before().fullmove_number(), 2);
+    }
+
+    #[test]
+    fn a_new_cmd_forgets_the_undone_ones() {
+        let mut game = Game::new();
+        play_san(&mut game, &["e4", "e5"]);
+        game.undo().unwrap();
+        assert!(game.can_redo());
+        play_san(&mut game, &["c5"]);
+        assert!(!game.can_redo());
+
+        game.exec_cmd(&Cmd::Resign(Camp::White)).unwrap();
+        assert!(game.result().is_some());
+        game.undo().unwrap();
+        assert!(game.result().is_none());
+        assert_eq!(game.cmds().len(), 2);
+        assert_ne!(game.to_fen(), START_FEN);
+    }
+}
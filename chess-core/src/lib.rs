@@ -1,7 +1,28 @@
 use lexer;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-
+use std::fmt;
+
+mod board;
+mod clock;
+mod error;
+mod fen;
+mod history;
+mod movegen;
+mod notation;
+mod perft;
+mod pgn;
+mod zobrist;
+
+pub use board::ChessBoard;
+pub use clock::{Clock, TimeControl};
+pub use error::ChessError;
+pub use fen::{FenError, START_FEN};
+pub use history::MoveRecord;
+pub use pgn::PgnError;
+
+// a square of the board, x is the file and y is the rank, both counted from 0:
+// (0,0) is a1, (7,0) is h1, (4,7) is e8; white starts on y=0 and y=1,
+// black on y=6 and y=7, see to_square() and from_square()
 #[derive(Serialize, Deserialize, Copy, Debug, Clone, Eq, Hash, PartialEq)]
 pub struct Vec2 {
     x: i32,
@@ -13,6 +34,14 @@ impl Vec2 {
         Vec2 { x, y }
     }
 
+    pub fn x(&self) -> i32 {
+        self.x
+    }
+
+    pub fn y(&self) -> i32 {
+        self.y
+    }
+
     pub fn between(from: Vec2, to: Vec2) -> Vec<Vec2> {
         if from == to {
             return Vec::new();
@@ -55,6 +84,29 @@ impl Vec2 {
         format!("({},{})", self.x, self.y)
     }
 
+    pub fn is_on_board(&self) -> bool {
+        (0..8).contains(&self.x) && (0..8).contains(&self.y)
+    }
+
+    // the algebraic name of the square, (0,0) is "a1" and (7,7) is "h8"
+    pub fn to_square(&self) -> String {
+        let file = (b'a' + self.x as u8) as char;
+        format!("{}{}", file, self.y + 1)
+    }
+
+    pub fn from_square(s: &str) -> Option<Self> {
+        let bytes = s.as_bytes();
+        if bytes.len() != 2 {
+            return None;
+        }
+        if !(b'a'..=b'h').contains(&bytes[0]) || !(b'1'..=b'8').contains(&bytes[1]) {
+            return None;
+        }
+        let x = (bytes[0] - b'a') as i32;
+        let y = (bytes[1] - b'1') as i32;
+        Some(Vec2::new(x, y))
+    }
+
     pub fn from_str(s: &str) -> Result<Self, ()> {
         let token_vec = lexer::Lexer::to_token_vec(s);
         if token_vec.len() != 5 {
@@ -102,7 +154,65 @@ fn board_cells() -> Vec<Vec2> {
 
 #[cfg(test)]
 mod tests {
-    use crate::Vec2;
+    use crate::{
+        Bishop, Camp, ChessBoard, ChessError, Cmd, Game, GameResult, King, Knight, MoveCmd, Pawn,
+        Piece, PieceKind, Queen, ResultReason, Rook, Stage, Vec2, PROMOTIONS,
+    };
+
+    fn empty_game(turn: Camp, pieces: Vec<Piece>) -> Game {
+        let mut game = Game {
+            stage: Stage {
+                turn,
+                result: None,
+                en_passant: None,
+                halfmove_clock: 0,
+                fullmove_number: 1,
+            },
+            board: ChessBoard::new(),
+            positions: Vec::new(),
+            start_fen: String::new(),
+            history: Vec::new(),
+            undone: Vec::new(),
+        };
+        for p in pieces {
+            game.board.insert_piece(p);
+        }
+        game.positions.push(game.hash());
+        game.start_fen = game.to_fen();
+        game
+    }
+
+    // kings and rooks on their initial squares, plus the given pieces
+    fn castling_game(turn: Camp, mut extra: Vec<Piece>) -> Game {
+        for (y, camp) in [(0, Camp::White), (7, Camp::Black)] {
+            extra.push(Piece::King(King::new(4, y, camp)));
+            extra.push(Piece::Rook(Rook::new(0, y, camp)));
+            extra.push(Piece::Rook(Rook::new(7, y, camp)));
+        }
+        empty_game(turn, extra)
+    }
+
+    fn castle(game: &mut Game, camp: Camp, to_x: i32) -> Result<(), ChessError> {
+        let y = if camp == Camp::White { 0 } else { 7 };
+        let cmd = Cmd::Move(MoveCmd::new(Vec2::new(4, y), Vec2::new(to_x, y)));
+        game.exec_cmd(&cmd)
+    }
+
+    // each move is (from.x, from.y, to.x, to.y)
+    fn play(game: &mut Game, moves: &[(i32, i32, i32, i32)]) {
+        for m in moves {
+            let cmd = Cmd::Move(MoveCmd::new(Vec2::new(m.0, m.1), Vec2::new(m.2, m.3)));
+            game.exec_cmd(&cmd).unwrap();
+        }
+    }
+
+    // each move in SAN, shared with the tests of the other modules
+    pub(crate) fn play_san(game: &mut Game, moves: &[&str]) {
+        for m in moves {
+            let cmd = game.parse_san(m).unwrap();
+            game.exec_cmd(&cmd).unwrap();
+        }
+    }
 
     #[test]
     fn vec2_conver() {
@@ -114,6 +224,326 @@ mod tests {
             panic!("Err when vec2 convert")
         }
     }
+
+    #[test]
+    fn promotion_is_part_of_the_move() {
+        let pieces = vec![
+            Piece::King(King::new(4, 0, Camp::White)),
+            Piece::King(King::new(7, 7, Camp::Black)),
+            Piece::Pawn(Pawn::new(0, 6, Camp::White)),
+        ];
+        let m = MoveCmd::new(Vec2::new(0, 6), Vec2::new(0, 7));
+
+        let mut game = empty_game(Camp::White, pieces);
+        assert!(game.exec_cmd(&Cmd::Move(m.clone())).is_err());
+        let to_king = m.clone().with_promotion(PieceKind::King);
+        assert!(game.exec_cmd(&Cmd::Move(to_king)).is_err());
+        let early = MoveCmd::new(Vec2::new(4, 0), Vec2::new(4, 1)).with_promotion(PieceKind::Queen);
+        assert!(game.exec_cmd(&Cmd::Move(early)).is_err());
+
+        for kind in PROMOTIONS {
+            let mut game = game.clone();
+            game.exec_cmd(&Cmd::Move(m.clone().with_promotion(kind)))
+                .unwrap();
+            let promoted = game.board.get_piece(Vec2::new(0, 7)).unwrap();
+            assert_eq!(promoted.kind(), kind);
+            assert!(promoted.get_base().is_white());
+            assert_eq!(game.stage.turn, Camp::Black);
+        }
+    }
+
+    #[test]
+    fn en_passant() {
+        let mut game = Game::new();
+        play(&mut game, &[(4, 1, 4, 3), (0, 6, 0, 5), (4, 3, 4, 4)]);
+        play(&mut game, &[(3, 6, 3, 4)]);
+
+        let capture = MoveCmd::new(Vec2::new(4, 4), Vec2::new(3, 5));
+        assert!(game
+            .valid_cmds()
+            .iter()
+            .any(|c| matches!(c, Cmd::Move(m) if m.from == capture.from && m.to == capture.to)));
+
+        game.exec_cmd(&Cmd::Move(capture)).unwrap();
+        assert!(game.board.get_piece(Vec2::new(3, 4)).is_none());
+        assert!(game.board.get_piece(Vec2::new(3, 5)).is_some());
+    }
+
+    #[test]
+    fn en_passant_only_right_after_double_push() {
+        let mut game = Game::new();
+        play(&mut game, &[(4, 1, 4, 3), (0, 6, 0, 5), (4, 3, 4, 4)]);
+        play(&mut game, &[(3, 6, 3, 4)]);
+        play(&mut game, &[(7, 1, 7, 2), (0, 5, 0, 4)]);
+
+        let capture = Cmd::Move(MoveCmd::new(Vec2::new(4, 4), Vec2::new(3, 5)));
+        assert!(game.exec_cmd(&capture).is_err());
+    }
+
+    #[test]
+    fn checkmate() {
+        let mut game = Game::new();
+        // fool's mate: 1. f3 e5 2. g4 Qh4#
+        play(&mut game, &[(5, 1, 5, 2), (4, 6, 4, 4), (6, 1, 6, 3)]);
+        play(&mut game, &[(3, 7, 7, 3)]);
+        assert_eq!(
+            game.result(),
+            Some(GameResult::BlackWins(ResultReason::Checkmate))
+        );
+        assert!(game
+            .exec_cmd(&Cmd::Move(MoveCmd::new(Vec2::new(0, 1), Vec2::new(0, 2))))
+            .is_err());
+    }
+
+    #[test]
+    fn stalemate_is_a_draw() {
+        let mut game = empty_game(
+            Camp::White,
+            vec![
+                Piece::King(King::new(7, 0, Camp::White)),
+                Piece::Queen(Queen::new(2, 4, Camp::White)),
+                Piece::King(King::new(0, 7, Camp::Black)),
+            ],
+        );
+        play(&mut game, &[(2, 4, 2, 6)]);
+        assert_eq!(
+            game.result(),
+            Some(GameResult::Draw(ResultReason::Stalemate))
+        );
+    }
+
+    #[test]
+    fn resignation() {
+        let mut game = Game::new();
+        game.exec_cmd(&Cmd::Resign(Camp::White)).unwrap();
+        assert_eq!(
+            game.result(),
+            Some(GameResult::BlackWins(ResultReason::Resignation))
+        );
+        assert!(game.exec_cmd(&Cmd::Resign(Camp::Black)).is_err());
+    }
+
+    #[test]
+    fn threefold_repetition_can_be_claimed() {
+        let mut game = Game::new();
+        assert!(game.exec_cmd(&Cmd::ClaimDraw).is_err());
+
+        let shuffle = [(1, 0, 2, 2), (1, 7, 2, 5), (2, 2, 1, 0), (2, 5, 1, 7)];
+        play(&mut game, &shuffle);
+        assert_eq!(game.claimable_draw(), None);
+        play(&mut game, &shuffle);
+        assert_eq!(game.claimable_draw(), Some(ResultReason::Repetition));
+
+        game.exec_cmd(&Cmd::ClaimDraw).unwrap();
+        assert_eq!(
+            game.result(),
+            Some(GameResult::Draw(ResultReason::Repetition))
+        );
+    }
+
+    #[test]
+    fn fifty_and_seventy_five_move_rules() {
+        let pieces = vec![
+            Piece::King(King::new(0, 0, Camp::White)),
+            Piece::Rook(Rook::new(7, 0, Camp::White)),
+            Piece::King(King::new(0, 7, Camp::Black)),
+        ];
+        let mut game = empty_game(Camp::White, pieces.clone());
+        game.stage.halfmove_clock = 99;
+        play(&mut game, &[(7, 0, 7, 1)]);
+        assert_eq!(game.claimable_draw(), Some(ResultReason::FiftyMove));
+        assert_eq!(game.result(), None);
+
+        let mut game = empty_game(Camp::White, pieces);
+        game.stage.halfmove_clock = 149;
+        play(&mut game, &[(7, 0, 7, 1)]);
+        assert_eq!(
+            game.result(),
+            Some(GameResult::Draw(ResultReason::FiftyMove))
+        );
+    }
+
+    #[test]
+    fn dead_positions() {
+        let kings = || {
+            vec![
+                Piece::King(King::new(0, 0, Camp::White)),
+                Piece::King(King::new(0, 7, Camp::Black)),
+            ]
+        };
+        assert!(empty_game(Camp::White, kings()).is_dead_position());
+
+        let mut pieces = kings();
+        pieces.push(Piece::Knight(Knight::new(4, 4, Camp::White)));
+        assert!(empty_game(Camp::White, pieces).is_dead_position());
+
+        let mut pieces = kings();
+        pieces.push(Piece::Bishop(Bishop::new(2, 0, Camp::White)));
+        pieces.push(Piece::Bishop(Bishop::new(5, 7, Camp::Black)));
+        assert!(empty_game(Camp::White, pieces).is_dead_position());
+
+        let mut pieces = kings();
+        pieces.push(Piece::Bishop(Bishop::new(2, 0, Camp::White)));
+        pieces.push(Piece::Bishop(Bishop::new(2, 7, Camp::Black)));
+        assert!(!empty_game(Camp::White, pieces).is_dead_position());
+
+        let mut pieces = kings();
+        pieces.push(Piece::Pawn(Pawn::new(4, 4, Camp::White)));
+        assert!(!empty_game(Camp::White, pieces).is_dead_position());
+    }
+
+    #[test]
+    fn flag_fall_needs_mating_material() {
+        let mut game = Game::new();
+        game.flag_fall(Camp::White).unwrap();
+        assert_eq!(
+            game.result(),
+            Some(GameResult::BlackWins(ResultReason::Timeout))
+        );
+        assert!(game.flag_fall(Camp::Black).is_err());
+
+        // black only has a knight
+        let mut game = empty_game(
+            Camp::White,
+            vec![
+                Piece::King(King::new(0, 0, Camp::White)),
+                Piece::Rook(Rook::new(0, 1, Camp::White)),
+                Piece::King(King::new(7, 7, Camp::Black)),
+                Piece::Knight(Knight::new(1, 5, Camp::Black)),
+            ],
+        );
+        game.flag_fall(Camp::White).unwrap();
+        assert_eq!(
+            game.result(),
+            Some(GameResult::Draw(ResultReason::InsufficientMaterial))
+        );
+    }
+
+    #[test]
+    fn capture_into_dead_position_ends_the_game() {
+        let mut game = empty_game(
+            Camp::White,
+            vec![
+                Piece::King(King::new(0, 0, Camp::White)),
+                Piece::King(King::new(7, 7, Camp::Black)),
+                Piece::Knight(Knight::new(1, 1, Camp::Black)),
+            ],
+        );
+        play(&mut game, &[(0, 0, 1, 1)]);
+        assert_eq!(
+            game.result(),
+            Some(GameResult::Draw(ResultReason::InsufficientMaterial))
+        );
+    }
+
+    #[test]
+    fn castling_both_wings() {
+        for camp in [Camp::White, Camp::Black] {
+            let y = if camp == Camp::White { 0 } else { 7 };
+            for (to_x, rook_from, rook_to) in [(2, 0, 3), (6, 7, 5)] {
+                let mut game = castling_game(camp, Vec::new());
+                castle(&mut game, camp, to_x).unwrap();
+                assert!(matches!(
+                    game.board.get_piece(Vec2::new(to_x, y)),
+                    Some(Piece::King(_))
+                ));
+                assert!(matches!(
+                    game.board.get_piece(Vec2::new(rook_to, y)),
+                    Some(Piece::Rook(_))
+                ));
+                assert!(game.board.get_piece(Vec2::new(rook_from, y)).is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn castling_queen_side_rook_may_pass_attacked_square() {
+        // only the rook crosses the b-file
+        for camp in [Camp::White, Camp::Black] {
+            let attacker = Piece::Rook(Rook::new(1, 4, camp.opposite()));
+            let mut game = castling_game(camp, vec![attacker]);
+            castle(&mut game, camp, 2).unwrap();
+        }
+    }
+
+    #[test]
+    fn castling_path_must_be_empty() {
+        for camp in [Camp::White, Camp::Black] {
+            let y = if camp == Camp::White { 0 } else { 7 };
+            for (to_x, blocker_x) in [(2, 1), (2, 2), (2, 3), (6, 5), (6, 6)] {
+                let blocker = Piece::Bishop(Bishop::new(blocker_x, y, camp));
+                let mut game = castling_game(camp, vec![blocker]);
+                assert!(castle(&mut game, camp, to_x).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn castling_out_of_through_or_into_check() {
+        for camp in [Camp::White, Camp::Black] {
+            // attacked file: the king, a transit square, then the target square
+            let cases = [
+                (2, 4, ChessError::CastlingOutOfCheck),
+                (2, 3, ChessError::CastlingThroughCheck),
+                (2, 2, ChessError::WouldLeaveKingInCheck),
+                (6, 4, ChessError::CastlingOutOfCheck),
+                (6, 5, ChessError::CastlingThroughCheck),
+                (6, 6, ChessError::WouldLeaveKingInCheck),
+            ];
+            for (to_x, attacked_x, err) in cases {
+                let attacker = Piece::Rook(Rook::new(attacked_x, 4, camp.opposite()));
+                let mut game = castling_game(camp, vec![attacker]);
+                assert_eq!(castle(&mut game, camp, to_x), Err(err));
+            }
+        }
+    }
+
+    #[test]
+    fn errors_tell_why_a_move_is_refused() {
+        let mut game = Game::new();
+        let mv = |x1, y1, x2, y2| Cmd::Move(MoveCmd::new(Vec2::new(x1, y1), Vec2::new(x2, y2)));
+        let bishop = ChessError::IllegalPieceMove {
+            piece: PieceKind::Bishop,
+        };
+        let knight = ChessError::IllegalPieceMove {
+            piece: PieceKind::Knight,
+        };
+
+        assert_eq!(
+            game.exec_cmd(&mv(4, 3, 4, 4)),
+            Err(ChessError::NoPieceAtSource)
+        );
+        assert_eq!(game.exec_cmd(&mv(4, 6, 4, 5)), Err(ChessError::NotYourTurn));
+        assert_eq!(
+            game.exec_cmd(&mv(0, 0, 0, 1)),
+            Err(ChessError::CaptureOwnPiece)
+        );
+        assert_eq!(game.exec_cmd(&mv(0, 0, 0, 2)), Err(ChessError::Blocked));
+        assert_eq!(game.exec_cmd(&mv(2, 0, 2, 2)), Err(bishop));
+        assert_eq!(game.exec_cmd(&mv(1, 0, 1, 2)), Err(knight));
+        assert_eq!(
+            game.exec_cmd(&mv(4, 1, i32::MIN, 0)),
+            Err(ChessError::OffBoard)
+        );
+        assert_eq!(
+            game.exec_cmd(&mv(4, 1, 4, i32::MAX)),
+            Err(ChessError::OffBoard)
+        );
+        assert_eq!(
+            game.exec_cmd(&Cmd::ClaimDraw),
+            Err(ChessError::NoDrawToClaim)
+        );
+
+        // 1. e4 f5 2. Qh5+ and black may not play 2... a6
+        play(&mut game, &[(4, 1, 4, 3), (5, 6, 5, 4), (3, 0, 7, 4)]);
+        assert_eq!(
+            game.exec_cmd(&mv(0, 6, 0, 5)),
+            Err(ChessError::WouldLeaveKingInCheck)
+        );
+
+        game.exec_cmd(&Cmd::Resign(Camp::Black)).unwrap();
+        assert_eq!(game.exec_cmd(&mv(0, 6, 0, 5)), Err(ChessError::GameOver));
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, Hash, PartialEq)]
@@ -123,7 +553,7 @@ pub enum Camp {
 }
 
 impl Camp {
-    fn opposite(&self) -> Camp {
+    pub fn opposite(&self) -> Camp {
         match *self {
             Camp::White => Camp::Black,
             Camp::Black => Camp::White,
@@ -204,37 +634,62 @@ impl Pawn {
         }
     }
 
-    pub fn deal_move(&self, to: Vec2, board: &mut ChessBoard) -> Result<(), &'static str> {
+    // en_passant: the square passed over by the last double pawn push, if any
+    pub fn deal_move(
+        &self,
+        to: Vec2,
+        board: &mut ChessBoard,
+        en_passant: Option<Vec2>,
+    ) -> Result<(), ChessError> {
         let relat_move = self.base.relative_move(to);
 
         if relat_move.x == 0 && relat_move.y == 1 {
             if board.get_piece(to).is_some() {
-                return Err("pawn can move to that pos, because there already has a piece");
+                return Err(ChessError::Blocked);
             }
         } else if relat_move.x == 0 && relat_move.y == 2 {
             if self.moved == true {
-                return Err("the pawn only move by 2 when first move");
+                return Err(ChessError::IllegalPieceMove {
+                    piece: PieceKind::Pawn,
+                });
             }
 
             if board.get_piece(to).is_some() {
-                return Err("pawn can move to that pos, because there already has a piece");
+                return Err(ChessError::Blocked);
             }
 
             for pos in Vec2::between(self.base.pos, to) {
                 if board.get_piece(pos).is_some() {
-                    return Err("blocked by some piece");
+                    return Err(ChessError::Blocked);
                 }
             }
         } else if abs(relat_move.x) == 1 && relat_move.y == 1 {
             if let Some(p) = board.get_piece(to) {
                 if p.get_base().is_camp(self.base.camp) {
-                    return Err("can not eat the piece belong to same camp");
+                    return Err(ChessError::CaptureOwnPiece);
+                }
+            } else if en_passant == Some(to) {
+                // the passed pawn stands beside us, on the file we move to
+                let passed_pos = Vec2::new(to.x, self.base.pos.y);
+                match board.get_piece(passed_pos) {
+                    Some(Piece::Pawn(p)) if !p.base.is_camp(self.base.camp) => {
+                        board.remove_piece(passed_pos)?;
+                    }
+                    _ => {
+                        return Err(ChessError::IllegalPieceMove {
+                            piece: PieceKind::Pawn,
+                        })
+                    }
                 }
             } else {
-                return Err("pawn only move to that pos , because there is no opposite camp piece");
+                return Err(ChessError::IllegalPieceMove {
+                    piece: PieceKind::Pawn,
+                });
             }
         } else {
-            return Err("pawn can not move like that");
+            return Err(ChessError::IllegalPieceMove {
+                piece: PieceKind::Pawn,
+            });
         }
 
         board.move_piece(self.base.pos, to)
@@ -270,11 +725,14 @@ impl King {
     }
 
     // return Option<(Vec2,Vec2)>
+    // the king moves from the e-file to the c-file or the g-file,
+    // the rook then jumps to the d-file or the f-file
     // None: when the castlng is illegal
     // Some: 1st Vec2: the old rook pos
     // 2st Vec2: the new rook pos
     pub fn is_castling(&self, to: Vec2, board: &ChessBoard) -> Result<(Vec2, Vec2), ()> {
-        if self.moved == true {
+        // only from its own square, whatever a board loaded says
+        if self.moved == true || self.base.pos.x != 4 {
             return Err(());
         }
 
@@ -285,7 +743,7 @@ impl King {
 
         let mut rook: Option<Rook> = None;
         let mut rook_pos = to.clone();
-        if to.x == 1 {
+        if to.x == 2 {
             rook_pos.x = 0;
         } else if to.x == 6 {
             rook_pos.x = 7;
@@ -295,7 +753,16 @@ impl King {
 
         if let Some(p) = board.get_piece(rook_pos) {
             if let Piece::Rook(k) = p {
-                rook = Some(k);
+                if k.base.is_camp(self.base.camp) {
+                    rook = Some(k);
+                }
+            }
+        }
+
+        // every square between the king and the rook must be empty
+        for pos in Vec2::between(self.base.pos, rook_pos) {
+            if board.get_piece(pos).is_some() {
+                return Err(());
             }
         }
 
@@ -305,7 +772,7 @@ impl King {
             } else {
                 let mut new_rook_pos = rook_pos.clone();
                 if rook_pos.x == 0 {
-                    new_rook_pos.x = 2;
+                    new_rook_pos.x = 3;
                 } else {
                     new_rook_pos.x = 5;
                 }
@@ -316,7 +783,7 @@ impl King {
         }
     }
 
-    pub fn deal_move(&self, to: Vec2, board: &mut ChessBoard) -> Result<(), &'static str> {
+    pub fn deal_move(&self, to: Vec2, board: &mut ChessBoard) -> Result<(), ChessError> {
         let relat_move = self.base.relative_move(to);
         if self.is_regular_move(relat_move) {
             return board.move_piece(self.base.pos, to);
@@ -325,7 +792,9 @@ impl King {
             board.move_piece(self.base.pos, to)?;
             return board.move_piece(old_rook_pos, new_rook_pos);
         }
-        return Err("king can not move like that");
+        return Err(ChessError::IllegalPieceMove {
+            piece: PieceKind::King,
+        });
     }
 }
 
@@ -365,15 +834,17 @@ impl Queen {
         false
     }
 
-    pub fn deal_move(&self, to: Vec2, board: &mut ChessBoard) -> Result<(), &'static str> {
+    pub fn deal_move(&self, to: Vec2, board: &mut ChessBoard) -> Result<(), ChessError> {
         let relat_move = self.base.relative_move(to);
         if !self.is_legal_move(relat_move) {
-            return Err("queen can not move like that");
+            return Err(ChessError::IllegalPieceMove {
+                piece: PieceKind::Queen,
+            });
         }
 
         for pos in Vec2::between(self.base.pos, to) {
             if board.get_piece(pos).is_some() {
-                return Err("blocked by some piece");
+                return Err(ChessError::Blocked);
             }
         }
 
@@ -405,14 +876,16 @@ impl Bishop {
         false
     }
 
-    pub fn deal_move(&self, to: Vec2, board: &mut ChessBoard) -> Result<(), &'static str> {
+    pub fn deal_move(&self, to: Vec2, board: &mut ChessBoard) -> Result<(), ChessError> {
         let relat_move = self.base.relative_move(to);
         if !self.is_legal_move(relat_move) {
-            return Err("king can not move like that");
+            return Err(ChessError::IllegalPieceMove {
+                piece: PieceKind::Bishop,
+            });
         }
         for pos in Vec2::between(self.base.pos, to) {
             if board.get_piece(pos).is_some() {
-                return Err("blocked by some piece");
+                return Err(ChessError::Blocked);
             }
         }
 
@@ -444,14 +917,16 @@ impl Rook {
         false
     }
 
-    pub fn deal_move(&self, to: Vec2, board: &mut ChessBoard) -> Result<(), &'static str> {
+    pub fn deal_move(&self, to: Vec2, board: &mut ChessBoard) -> Result<(), ChessError> {
         let relat_move = self.base.relative_move(to);
         if !self.is_legal_move(relat_move) {
-            return Err("rook can not move like that");
+            return Err(ChessError::IllegalPieceMove {
+                piece: PieceKind::Rook,
+            });
         }
         for pos in Vec2::between(self.base.pos, to) {
             if board.get_piece(pos).is_some() {
-                return Err("blocked by some piece");
+                return Err(ChessError::Blocked);
             }
         }
 
@@ -483,10 +958,12 @@ impl Knight {
         false
     }
 
-    pub fn deal_move(&self, to: Vec2, board: &mut ChessBoard) -> Result<(), &'static str> {
+    pub fn deal_move(&self, to: Vec2, board: &mut ChessBoard) -> Result<(), ChessError> {
         let relat_move = self.base.relative_move(to);
         if !self.is_legal_move(relat_move) {
-            return Err("king can not move like that");
+            return Err(ChessError::IllegalPieceMove {
+                piece: PieceKind::Knight,
+            });
         }
         board.move_piece(self.base.pos, to)
     }
@@ -502,7 +979,77 @@ pub enum Piece {
     Rook(Rook),
 }
 
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, Hash, PartialEq)]
+pub enum PieceKind {
+    Pawn,
+    King,
+    Queen,
+    Bishop,
+    Knight,
+    Rook,
+}
+
+// the kinds a pawn can promote to, the strongest first
+pub const PROMOTIONS: [PieceKind; 4] = [
+    PieceKind::Queen,
+    PieceKind::Rook,
+    PieceKind::Bishop,
+    PieceKind::Knight,
+];
+
+impl PieceKind {
+    // the uppercase letter of the kind in algebraic notation
+    pub fn letter(&self) -> char {
+        match self {
+            PieceKind::Pawn => 'P',
+            PieceKind::King => 'K',
+            PieceKind::Queen => 'Q',
+            PieceKind::Bishop => 'B',
+            PieceKind::Knight => 'N',
+            PieceKind::Rook => 'R',
+        }
+    }
+
+    pub fn from_letter(c: char) -> Option<PieceKind> {
+        match c.to_ascii_uppercase() {
+            'P' => Some(PieceKind::Pawn),
+            'K' => Some(PieceKind::King),
+            'Q' => Some(PieceKind::Queen),
+            'B' => Some(PieceKind::Bishop),
+            'N' => Some(PieceKind::Knight),
+            'R' => Some(PieceKind::Rook),
+            _ => None,
+        }
+    }
+
+    pub fn is_promotion(&self) -> bool {
+        PROMOTIONS.contains(self)
+    }
+}
+
 impl Piece {
+    pub fn new(kind: PieceKind, x: i32, y: i32, camp: Camp) -> Piece {
+        match kind {
+            PieceKind::Pawn => Piece::Pawn(Pawn::new(x, y, camp)),
+            PieceKind::King => Piece::King(King::new(x, y, camp)),
+            PieceKind::Queen => Piece::Queen(Queen::new(x, y, camp)),
+            PieceKind::Bishop => Piece::Bishop(Bishop::new(x, y, camp)),
+            PieceKind::Knight => Piece::Knight(Knight::new(x, y, camp)),
+            PieceKind::Rook => Piece::Rook(Rook::new(x, y, camp)),
+        }
+    }
+
+    pub fn kind(&self) -> PieceKind {
+        match self {
+            Piece::Pawn(_) => PieceKind::Pawn,
+            Piece::King(_) => PieceKind::King,
+            Piece::Queen(_) => PieceKind::Queen,
+            Piece::Bishop(_) => PieceKind::Bishop,
+            Piece::Knight(_) => PieceKind::Knight,
+            Piece::Rook(_) => PieceKind::Rook,
+        }
+    }
+
     pub fn get_base(&self) -> BasePiece {
         match self {
             Piece::Pawn(p) => p.base.clone(),
@@ -515,6 +1062,16 @@ impl Piece {
         }
     }
 
+    // the letter of the piece in algebraic notation, uppercase for white
+    pub fn symbol(&self) -> char {
+        let c = self.kind().letter();
+        if self.get_base().is_white() {
+            c
+        } else {
+            c.to_ascii_lowercase()
+        }
+    }
+
     pub fn change_pos(&mut self, newpos: Vec2) {
         match *self {
             Piece::Pawn(ref mut p) => {
@@ -537,9 +1094,14 @@ impl Piece {
         }
     }
 
-    pub fn deal_move(&self, to: Vec2, board: &mut ChessBoard) -> Result<(), &'static str> {
+    pub fn deal_move(
+        &self,
+        to: Vec2,
+        board: &mut ChessBoard,
+        stage: &Stage,
+    ) -> Result<(), ChessError> {
         match self {
-            Piece::Pawn(p) => p.deal_move(to, board),
+            Piece::Pawn(p) => p.deal_move(to, board, stage.en_passant),
             Piece::King(p) => p.deal_move(to, board),
             Piece::Queen(p) => p.deal_move(to, board),
 
@@ -553,11 +1115,36 @@ impl Piece {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Stage {
     turn: Camp,
-    is_promotion: bool,
-    winner: Option<Camp>,
+    result: Option<GameResult>,
+    // the square passed over by a pawn which moved by 2 in the last turn
+    en_passant: Option<Vec2>,
+    // turns since the last pawn move or capture
+    halfmove_clock: u32,
+    // starts at 1, incremented after every black move
+    fullmove_number: u32,
 }
 
 impl Stage {
+    pub fn turn(&self) -> Camp {
+        self.turn
+    }
+
+    pub fn result(&self) -> Option<GameResult> {
+        self.result
+    }
+
+    pub fn en_passant(&self) -> Option<Vec2> {
+        self.en_passant
+    }
+
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    pub fn fullmove_number(&self) -> u32 {
+        self.fullmove_number
+    }
+
     fn change_turn(&mut self) {
         match self.clone().turn {
             Camp::Black => {
@@ -571,79 +1158,66 @@ impl Stage {
     }
 }
 
-const ERR_PIECE_NOT_FOUND: &'static str = "piece not found";
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct ChessBoard {
-    pub board: HashMap<String, Piece>,
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ResultReason {
+    Checkmate,
+    Stalemate,
+    Resignation,
+    Timeout,
+    Agreement,
+    Repetition,
+    FiftyMove,
+    InsufficientMaterial,
 }
 
-impl ChessBoard {
-    fn new() -> Self {
-        return ChessBoard {
-            board: HashMap::new(),
-        };
-    }
-
-    fn insert_piece(&mut self, p: Piece) {
-        self.board.insert(p.get_base().pos.to_string(), p);
-    }
-
-    fn move_piece(&mut self, from: Vec2, to: Vec2) -> Result<(), &'static str> {
-        match self.remove_piece(from) {
-            Ok(mut p) => {
-                p.change_pos(to);
-                self.board.insert(to.to_string(), p);
-                Ok(())
-            }
-
-            Err(x) => Err(x),
-        }
-    }
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GameResult {
+    WhiteWins(ResultReason),
+    BlackWins(ResultReason),
+    Draw(ResultReason),
+}
 
-    fn remove_piece(&mut self, pos: Vec2) -> Result<Piece, &'static str> {
-        if let Some(res) = self.board.get(pos.to_string().as_str()) {
-            let p: Piece;
-            p = res.clone();
-            self.board.remove(&pos.to_string()).unwrap();
-            Ok(p)
-        } else {
-            Err(ERR_PIECE_NOT_FOUND)
+impl GameResult {
+    fn win(camp: Camp, reason: ResultReason) -> Self {
+        match camp {
+            Camp::White => GameResult::WhiteWins(reason),
+            Camp::Black => GameResult::BlackWins(reason),
         }
     }
 
-    fn get_piece(&self, pos: Vec2) -> Option<Piece> {
-        if let Some(p) = self.board.get(pos.to_string().as_str()) {
-            Some(p.clone())
-        } else {
-            None
+    // None when the game is drawn
+    pub fn winner(&self) -> Option<Camp> {
+        match self {
+            GameResult::WhiteWins(_) => Some(Camp::White),
+            GameResult::BlackWins(_) => Some(Camp::Black),
+            GameResult::Draw(_) => None,
         }
     }
 
-    fn get_king_of_camp(&self, camp: Camp) -> King {
-        for ele in &self.board {
-            match ele.1 {
-                Piece::King(p) => {
-                    if p.base.is_camp(camp) {
-                        return p.clone();
-                    }
-                }
-
-                _ => {}
-            }
+    pub fn reason(&self) -> ResultReason {
+        match *self {
+            GameResult::WhiteWins(r) | GameResult::BlackWins(r) | GameResult::Draw(r) => r,
         }
-        panic!("king not found")
     }
+}
 
-    fn get_piece_of_camp(&self, camp: Camp) -> Vec<Piece> {
-        let mut pieces = Vec::new();
-        for ele in &self.board {
-            let p = ele.1;
-            if p.get_base().is_camp(camp) {
-                pieces.push(p.clone())
-            }
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self.reason() {
+            ResultReason::Checkmate => "checkmate",
+            ResultReason::Stalemate => "stalemate",
+            ResultReason::Resignation => "resignation",
+            ResultReason::Timeout => "timeout",
+            ResultReason::Agreement => "agreement",
+            ResultReason::Repetition => "repetition",
+            ResultReason::FiftyMove => "the fifty-move rule",
+            ResultReason::InsufficientMaterial => "insufficient material",
+        };
+        match self {
+            GameResult::WhiteWins(_) => write!(f, "white wins by {}", reason),
+            GameResult::BlackWins(_) => write!(f, "black wins by {}", reason),
+            GameResult::Draw(_) => write!(f, "draw by {}", reason),
         }
-        pieces
     }
 }
 
@@ -651,123 +1225,57 @@ impl ChessBoard {
 pub struct Game {
     stage: Stage,
     pub board: ChessBoard,
+    // hash() at the beginning of every turn
+    positions: Vec<u64>,
+    start_fen: String,
+    history: Vec<MoveRecord>,
+    // the cmds taken back by undo(), the last one first to redo
+    undone: Vec<Cmd>,
 }
 
 impl Game {
     pub fn new() -> Self {
-        let mut game = Game {
-            stage: Stage {
-                turn: Camp::White,
-                is_promotion: false,
-                winner: None,
-            },
-            board: ChessBoard::new(),
-        };
-
-        // pawn initilaztion
-        game.board
-            .insert_piece(Piece::Pawn(Pawn::new(0, 1, Camp::White)));
-
-        game.board
-            .insert_piece(Piece::Pawn(Pawn::new(0, 6, Camp::Black)));
-
-        game.board
-            .insert_piece(Piece::Pawn(Pawn::new(1, 1, Camp::White)));
-
-        game.board
-            .insert_piece(Piece::Pawn(Pawn::new(1, 6, Camp::Black)));
-
-        game.board
-            .insert_piece(Piece::Pawn(Pawn::new(2, 1, Camp::White)));
-
-        game.board
-            .insert_piece(Piece::Pawn(Pawn::new(2, 6, Camp::Black)));
-        game.board
-            .insert_piece(Piece::Pawn(Pawn::new(3, 1, Camp::White)));
-
-        game.board
-            .insert_piece(Piece::Pawn(Pawn::new(3, 6, Camp::Black)));
-        game.board
-            .insert_piece(Piece::Pawn(Pawn::new(4, 1, Camp::White)));
-
-        game.board
-            .insert_piece(Piece::Pawn(Pawn::new(4, 6, Camp::Black)));
-
-        game.board
-            .insert_piece(Piece::Pawn(Pawn::new(5, 1, Camp::White)));
-
-        game.board
-            .insert_piece(Piece::Pawn(Pawn::new(5, 6, Camp::Black)));
-
-        game.board
-            .insert_piece(Piece::Pawn(Pawn::new(6, 1, Camp::White)));
-
-        game.board
-            .insert_piece(Piece::Pawn(Pawn::new(6, 6, Camp::Black)));
-
-        game.board
-            .insert_piece(Piece::Pawn(Pawn::new(7, 1, Camp::White)));
-
-        game.board
-            .insert_piece(Piece::Pawn(Pawn::new(7, 6, Camp::Black)));
-
-        // king initilaztion
-        game.board
-            .insert_piece(Piece::King(King::new(3, 0, Camp::White)));
-
-        game.board
-            .insert_piece(Piece::King(King::new(3, 7, Camp::Black)));
-
-        // left side
-
-        game.board
-            .insert_piece(Piece::Rook(Rook::new(0, 0, Camp::White)));
-
-        game.board
-            .insert_piece(Piece::Rook(Rook::new(0, 7, Camp::Black)));
-
-        game.board
-            .insert_piece(Piece::Knight(Knight::new(1, 0, Camp::White)));
-
-        game.board
-            .insert_piece(Piece::Knight(Knight::new(1, 7, Camp::Black)));
-
-        game.board
-            .insert_piece(Piece::Bishop(Bishop::new(2, 0, Camp::White)));
-
-        game.board
-            .insert_piece(Piece::Bishop(Bishop::new(2, 7, Camp::Black)));
-
-        // right side
-        game.board
-            .insert_piece(Piece::Queen(Queen::new(4, 0, Camp::White)));
-
-        game.board
-            .insert_piece(Piece::Queen(Queen::new(4, 7, Camp::Black)));
-
-        game.board
-            .insert_piece(Piece::Rook(Rook::new(7, 0, Camp::White)));
-
-        game.board
-            .insert_piece(Piece::Rook(Rook::new(7, 7, Camp::Black)));
-
-        game.board
-            .insert_piece(Piece::Knight(Knight::new(6, 0, Camp::White)));
-
-        game.board
-            .insert_piece(Piece::Knight(Knight::new(6, 7, Camp::Black)));
+        Game::from_fen(START_FEN).unwrap()
+    }
 
-        game.board
-            .insert_piece(Piece::Bishop(Bishop::new(5, 0, Camp::White)));
+    pub fn result(&self) -> Option<GameResult> {
+        self.stage.result
+    }
 
-        game.board
-            .insert_piece(Piece::Bishop(Bishop::new(5, 7, Camp::Black)));
+    pub fn stage(&self) -> &Stage {
+        &self.stage
+    }
 
-        game
+    // whether the king of the side to move is attacked
+    pub fn in_check(&self) -> bool {
+        self.after_check_king_dangerous().is_err()
     }
 
-    pub fn exec_cmd(&mut self, c: &Cmd) -> Result<(), &'static str> {
+    pub fn exec_cmd(&mut self, c: &Cmd) -> Result<(), ChessError> {
+        // before any piece logic, which computes with the squares
+        if let Cmd::Move(m) = c {
+            if !m.from.is_on_board() || !m.to.is_on_board() {
+                return Err(ChessError::OffBoard);
+            }
+        }
+        let record = self.record_of(c);
+        if let Cmd::Resign(camp) = c {
+            self.deal_resign(*camp)?;
+            self.push_history(record);
+            return Ok(());
+        }
+        if let Cmd::ClaimDraw = c {
+            self.deal_claim_draw()?;
+            self.push_history(record);
+            return Ok(());
+        }
+
         let game_backup = self.clone();
+        let pieces_count = self.board.piece_count();
+        let pawn_moved = match c {
+            Cmd::Move(x) => matches!(self.board.get_piece(x.from), Some(Piece::Pawn(_))),
+            _ => false,
+        };
 
         let res = self.exec_cmd_pre(c);
         if res.is_err() {
@@ -784,169 +1292,349 @@ impl Game {
         // the cmd is accepted, change state  now
 
         if let Cmd::Move(x) = c {
-            if x.to.y == 0 || x.to.y == 7 {
-                if let Some(Piece::Pawn(_)) = self.board.get_piece(x.to) {
-                    self.stage.is_promotion = true;
-                }
-            }
+            let captured = self.board.piece_count() < pieces_count;
+            self.advance_stage(x, pawn_moved, captured);
+        }
+        self.positions.push(self.hash());
+        self.update_result();
+
+        self.push_history(record);
+
+        Ok(())
+    }
+
+    // the en passant square, the move counters and the side to move after a move
+    fn advance_stage(&mut self, m: &MoveCmd, pawn_moved: bool, captured: bool) {
+        self.stage.en_passant = None;
+        if pawn_moved && abs(m.to.y - m.from.y) == 2 {
+            self.stage.en_passant = Some(Vec2::new(m.from.x, (m.from.y + m.to.y) / 2));
+        }
+
+        if pawn_moved || captured {
+            self.stage.halfmove_clock = 0;
         } else {
-            self.stage.is_promotion = false;
+            self.stage.halfmove_clock += 1;
         }
 
-        if !self.stage.is_promotion {
-            self.stage.change_turn();
-            let cmds = self.valid_cmds();
-            if cmds.len() == 0 {
-                self.stage.winner = Some(self.stage.turn.opposite());
-            } else {
-                println!("{:?}", cmds);
-            }
+        self.stage.change_turn();
+        if self.stage.turn == Camp::White {
+            self.stage.fullmove_number += 1;
         }
+    }
+
+    // every accepted cmd, in order
+    pub fn cmds(&self) -> Vec<Cmd> {
+        self.history.iter().map(|r| r.cmd.clone()).collect()
+    }
+
+    // the FEN of the position the game started from
+    pub fn start_fen(&self) -> &str {
+        &self.start_fen
+    }
 
-        if self.stage.winner.is_some() {
-            println!("finished winner{:?}", self.stage.winner)
+    // called when a new turn begins, ends the game if the side to move has no valid cmd
+    fn update_result(&mut self) {
+        let cmds = self.valid_cmds();
+        if !cmds.is_empty() {
+            self.update_draw();
+            return;
         }
 
-        Ok(())
+        if self.after_check_king_dangerous().is_err() {
+            let winner = self.stage.turn.opposite();
+            self.stage.result = Some(GameResult::win(winner, ResultReason::Checkmate));
+        } else {
+            self.stage.result = Some(GameResult::Draw(ResultReason::Stalemate));
+        }
     }
 
-    fn exec_cmd_pre(&mut self, c: &Cmd) -> Result<(), &'static str> {
-        match c {
-            Cmd::Move(x) => self.deal_move(x.from, x.to),
+    fn update_draw(&mut self) {
+        if self.is_dead_position() {
+            self.stage.result = Some(GameResult::Draw(ResultReason::InsufficientMaterial));
+        } else if self.stage.halfmove_clock >= 150 {
+            // seventy-five-move rule
+            self.stage.result = Some(GameResult::Draw(ResultReason::FiftyMove));
+        } else if self.repetitions() >= 5 {
+            self.stage.result = Some(GameResult::Draw(ResultReason::Repetition));
+        }
+    }
 
-            Cmd::Promote(p) => self.deal_promote(p.from,p.to.clone()),
+    // a draw the side to move may claim with Cmd::ClaimDraw
+    pub fn claimable_draw(&self) -> Option<ResultReason> {
+        if self.stage.result.is_some() {
+            return None;
+        }
+        if self.stage.halfmove_clock >= 100 {
+            return Some(ResultReason::FiftyMove);
         }
+        if self.repetitions() >= 3 {
+            return Some(ResultReason::Repetition);
+        }
+        None
     }
 
-    fn deal_promote(&mut self,from: Vec2, piece: String) -> Result<(), &'static str> {
-        if !self.stage.is_promotion {
-            return Err("illegal promotion in this turn");
+    fn deal_claim_draw(&mut self) -> Result<(), ChessError> {
+        if self.stage.result.is_some() {
+            return Err(ChessError::GameOver);
         }
-        if let Some(Piece::Pawn(pawn)) = self.board.get_piece(from){
-            if pawn.base.is_camp(self.stage.turn)&&(pawn.base.pos.y == 0 || pawn.base.pos.y == 7){
-                match piece.as_str() {
-                    "queen" | "Queen"   => { self.board.insert_piece(Piece::Queen(Queen::new(pawn.base.pos.x, pawn.base.pos.y, pawn.base.camp)))}
-                    "knight" | "Knight" => { self.board.insert_piece(Piece::Knight(Knight::new(pawn.base.pos.x, pawn.base.pos.y, pawn.base.camp)))}
-                    "bishop" | "Bishop" => { self.board.insert_piece(Piece::Bishop(Bishop::new(pawn.base.pos.x, pawn.base.pos.y, pawn.base.camp)))}
-                    _ => {panic!("wrong piece type")}
-                };
-                return Ok(())
+        match self.claimable_draw() {
+            Some(reason) => {
+                self.stage.result = Some(GameResult::Draw(reason));
+                Ok(())
             }
+            None => Err(ChessError::NoDrawToClaim),
         }
-        Err("fail to promote this peice")
+    }
 
+    // hash() at the beginning of every turn, the current position last
+    pub fn position_hashes(&self) -> &[u64] {
+        &self.positions
     }
 
-    fn after_check_king_dangerous(&self) -> Result<(), &'static str> {
-        let our_king = self.board.get_king_of_camp(self.stage.turn);
-        let opposite_piece = self.board.get_piece_of_camp(self.stage.turn.opposite());
+    // how many times the current position has appeared
+    fn repetitions(&self) -> usize {
+        let hash = self.hash();
+        self.positions.iter().filter(|h| **h == hash).count()
+    }
 
-        for p in opposite_piece {
-            let mut game_copy = self.clone();
-            game_copy.stage.change_turn();
-            let cmd = Cmd::Move(MoveCmd {
-                from: p.get_base().pos,
-                to: our_king.base.pos,
-            });
+    // neither camp can checkmate: only kings, or a single minor piece,
+    // or bishops which all stand on squares of the same colour
+    fn is_dead_position(&self) -> bool {
+        let mut knights = 0;
+        let mut bishop_colors = Vec::new();
+        for p in &self.board.pieces() {
+            match p {
+                Piece::King(_) => {}
+                Piece::Knight(_) => knights += 1,
+                Piece::Bishop(b) => bishop_colors.push((b.base.pos.x + b.base.pos.y) % 2),
+                _ => return false,
+            }
+        }
 
-            let res = game_copy.exec_cmd_pre(&cmd);
-            if res.is_ok() {
-                println!("your can not make your king be killed {:?}", p);
-                return Err("your can not make your king be killed");
-            } else {
+        if knights + bishop_colors.len() <= 1 {
+            return true;
+        }
+        knights == 0 && bishop_colors.iter().all(|c| *c == bishop_colors[0])
+    }
+
+    // "K"/"Q": white may still castle with the rook on x=7/x=0, "k"/"q" for black
+    fn castling_rights(&self) -> String {
+        let mut rights = String::new();
+        for camp in [Camp::White, Camp::Black] {
+            let king = match self.board.get_king_of_camp_opt(camp) {
+                Some(k) if !k.moved => k,
+                _ => continue,
+            };
+            for (rook_x, c) in [(7, 'K'), (0, 'Q')] {
+                let rook_pos = Vec2::new(rook_x, king.base.pos.y);
+                if let Some(Piece::Rook(r)) = self.board.get_piece(rook_pos) {
+                    if !r.moved && r.base.is_camp(camp) {
+                        rights.push(if camp == Camp::White {
+                            c
+                        } else {
+                            c.to_ascii_lowercase()
+                        });
+                    }
+                }
             }
         }
+        rights
+    }
 
+    fn deal_resign(&mut self, camp: Camp) -> Result<(), ChessError> {
+        if self.stage.result.is_some() {
+            return Err(ChessError::GameOver);
+        }
+        let winner = camp.opposite();
+        self.stage.result = Some(GameResult::win(winner, ResultReason::Resignation));
         Ok(())
     }
 
-    fn valid_cmds(&mut self) -> Vec<Cmd> {
-        let mut cmds = Vec::new();
+    // the time of the camp ran out: the opponent wins, or draws when it has
+    // nothing left to mate with
+    pub fn flag_fall(&mut self, camp: Camp) -> Result<(), ChessError> {
+        if self.stage.result.is_some() {
+            return Err(ChessError::GameOver);
+        }
+        let opponent = camp.opposite();
+        self.stage.result = Some(if self.can_mate(opponent) {
+            GameResult::win(opponent, ResultReason::Timeout)
+        } else {
+            GameResult::Draw(ResultReason::InsufficientMaterial)
+        });
+        Ok(())
+    }
 
-        let froms = board_cells();
-        let tos = board_cells();
+    // a pawn or a major piece mates, a lone minor piece or bishops all on
+    // squares of the same colour never do
+    fn can_mate(&self, camp: Camp) -> bool {
+        let mut knights = 0;
+        let mut bishop_colors = Vec::new();
+        for p in self
+            .board
+            .pieces()
+            .iter()
+            .filter(|p| p.get_base().is_camp(camp))
+        {
+            match p {
+                Piece::King(_) => {}
+                Piece::Knight(_) => knights += 1,
+                Piece::Bishop(b) => bishop_colors.push((b.base.pos.x + b.base.pos.y) % 2),
+                _ => return true,
+            }
+        }
+        (knights > 0 && knights + bishop_colors.len() >= 2)
+            || bishop_colors.iter().any(|c| *c != bishop_colors[0])
+    }
 
-        for from in froms.clone() {
-            for to in tos.clone() {
-                let mut game_copy = self.clone();
-                if game_copy.deal_move(from, to).is_ok() {
-                    if game_copy.after_check_king_dangerous().is_ok() {
-                        cmds.push(Cmd::Move(MoveCmd { from, to }));
-                    }
+    fn exec_cmd_pre(&mut self, c: &Cmd) -> Result<(), ChessError> {
+        match c {
+            Cmd::Move(x) => {
+                self.deal_move(x.from, x.to)?;
+                self.deal_promote(x.to, x.promotion)
+            }
+
+            Cmd::Resign(camp) => self.deal_resign(*camp),
+
+            Cmd::ClaimDraw => self.deal_claim_draw(),
+        }
+    }
+
+    // a pawn which reached the last rank is replaced by the piece chosen in its move
+    fn deal_promote(&mut self, to: Vec2, promotion: Option<PieceKind>) -> Result<(), ChessError> {
+        let camp = match self.board.get_piece(to) {
+            Some(Piece::Pawn(p)) if to.y == 0 || to.y == 7 => p.base.camp,
+            _ => {
+                if promotion.is_some() {
+                    return Err(ChessError::IllegalPromotion);
                 }
+                return Ok(());
             }
+        };
+
+        match promotion {
+            Some(kind) if kind.is_promotion() => {
+                self.board.insert_piece(Piece::new(kind, to.x, to.y, camp));
+                Ok(())
+            }
+            Some(_) => Err(ChessError::IllegalPromotion),
+            None => Err(ChessError::PromotionRequired),
         }
+    }
 
-        cmds
+    // a board without the king, which no legal game reaches, is never in check
+    fn after_check_king_dangerous(&self) -> Result<(), ChessError> {
+        let Some(our_king) = self.board.get_king_of_camp_opt(self.stage.turn) else {
+            return Ok(());
+        };
+        if self
+            .board
+            .is_attacked(our_king.base.pos, self.stage.turn.opposite())
+        {
+            return Err(ChessError::WouldLeaveKingInCheck);
+        }
+        Ok(())
     }
 
-    fn exec_cmd_after(&mut self) -> Result<(), &'static str> {
+    fn exec_cmd_after(&mut self) -> Result<(), ChessError> {
         self.after_check_king_dangerous()
     }
 
-    fn deal_move(&mut self, from: Vec2, to: Vec2) -> Result<(), &'static str> {
+    fn deal_move(&mut self, from: Vec2, to: Vec2) -> Result<(), ChessError> {
         self.deal_move_target_confirm(from, to)?;
         self.deal_move_turn(from)?;
         self.deal_move_piece(from, to)
     }
 
-    fn deal_move_target_confirm(&mut self, from: Vec2, to: Vec2) -> Result<(), &'static str> {
+    fn deal_move_target_confirm(&mut self, from: Vec2, to: Vec2) -> Result<(), ChessError> {
         if self.board.get_piece(from).is_some() {
             if let Some(to) = self.board.get_piece(to) {
                 if to.get_base().is_camp(self.stage.turn) {
-                    return Err("can not eat the piece belong to same camp");
+                    return Err(ChessError::CaptureOwnPiece);
                 }
             }
 
             return Ok(());
         } else {
-            return Err("can not find the picked piece");
+            return Err(ChessError::NoPieceAtSource);
         }
     }
 
-    fn deal_move_turn(&mut self, from: Vec2) -> Result<(), &'static str> {
-        if self.stage.winner.is_some() {
-            return Err("game finished");
-        }
-        if self.stage.is_promotion {
-            return Err("promotion mode");
+    fn deal_move_turn(&mut self, from: Vec2) -> Result<(), ChessError> {
+        if self.stage.result.is_some() {
+            return Err(ChessError::GameOver);
         }
 
         let piece = self.board.get_piece(from).unwrap();
         if piece.get_base().is_your_turn(&self.stage) {
             Ok(())
         } else {
-            Err("not your turn")
+            Err(ChessError::NotYourTurn)
         }
     }
 
-    fn deal_move_piece(&mut self, from: Vec2, to: Vec2) -> Result<(), &'static str> {
+    fn deal_move_piece(&mut self, from: Vec2, to: Vec2) -> Result<(), ChessError> {
         let piece = self.board.get_piece(from).unwrap();
 
-        piece.deal_move(to, &mut self.board)
+        if let Piece::King(k) = &piece {
+            if !k.is_regular_move(k.base.relative_move(to))
+                && k.is_castling(to, &self.board).is_ok()
+            {
+                self.deal_castling_safety(from, to)?;
+            }
+        }
+
+        piece.deal_move(to, &mut self.board, &self.stage)
+    }
+
+    // the king can not castle out of check or pass through an attacked square,
+    // landing on an attacked square is refused later by exec_cmd_after
+    fn deal_castling_safety(&self, from: Vec2, to: Vec2) -> Result<(), ChessError> {
+        if self.after_check_king_dangerous().is_err() {
+            return Err(ChessError::CastlingOutOfCheck);
+        }
+
+        for pos in Vec2::between(from, to) {
+            let mut game_copy = self.clone();
+            game_copy.board.move_piece(from, pos)?;
+            if game_copy.after_check_king_dangerous().is_err() {
+                return Err(ChessError::CastlingThroughCheck);
+            }
+        }
+
+        Ok(())
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct MoveCmd {
-    from: Vec2,
-    to: Vec2,
+    pub from: Vec2,
+    pub to: Vec2,
+    // the piece a pawn reaching the last rank becomes
+    #[serde(default)]
+    pub promotion: Option<PieceKind>,
 }
 
 impl MoveCmd {
     pub fn new(from: Vec2, to: Vec2) -> MoveCmd {
-        MoveCmd { from, to }
+        MoveCmd {
+            from,
+            to,
+            promotion: None,
+        }
     }
-}
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct PromoteCmd {
-    pub from: Vec2,
-    pub to: String,
+    pub fn with_promotion(mut self, kind: PieceKind) -> MoveCmd {
+        self.promotion = Some(kind);
+        self
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub enum Cmd {
     Move(MoveCmd),
-    Promote(PromoteCmd),
+    // the given camp gives up the game
+    Resign(Camp),
+    // the side to move claims a draw by threefold repetition or the fifty-move rule
+    ClaimDraw,
 }
@@ -1 +1,38 @@
-fn main() {}
+use std::env;
+use std::process;
+
+use chess_core::{Game, START_FEN};
+
+// prints the divide of a position, to compare the move generation with another engine:
+// cargo run -p chess-core --release -- <depth> [fen]
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let depth = match args.first().and_then(|d| d.parse::<u32>().ok()) {
+        Some(d) => d,
+        None => {
+            eprintln!("usage: chess-core <depth> [fen]");
+            process::exit(2);
+        }
+    };
+    let fen = if args.len() > 1 {
+        args[1..].join(" ")
+    } else {
+        START_FEN.to_string()
+    };
+
+    let game = match Game::from_fen(&fen) {
+        Ok(g) => g,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+
+    let mut total = 0;
+    for (m, nodes) in game.divide(depth) {
+        println!("{}: {}", m, nodes);
+        total += nodes;
+    }
+    println!();
+    println!("nodes: {}", total);
+}
@@ -0,0 +1,299 @@
+use crate::{board_cells, Camp, ChessBoard, Cmd, Game, MoveCmd, Piece, Vec2, PROMOTIONS};
+
+const KNIGHT_STEPS: [(i32, i32); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+
+const KING_STEPS: [(i32, i32); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+
+const ROOK_DIRECTIONS: [(i32, i32); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+
+const BISHOP_DIRECTIONS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+fn on_board(x: i32, y: i32) -> bool {
+    Vec2::new(x, y).is_on_board()
+}
+
+// the rank direction pawns of the camp move to
+fn forward(camp: Camp) -> i32 {
+    if camp == Camp::White {
+        1
+    } else {
+        -1
+    }
+}
+
+impl ChessBoard {
+    // whether a piece of the camp could capture on the square
+    pub fn is_attacked(&self, pos: Vec2, by: Camp) -> bool {
+        let attacker = |x: i32, y: i32| {
+            if !on_board(x, y) {
+                return None;
+            }
+            self.get_piece(Vec2::new(x, y))
+                .filter(|p| p.get_base().is_camp(by))
+        };
+
+        for (dx, dy) in KNIGHT_STEPS {
+            if let Some(Piece::Knight(_)) = attacker(pos.x + dx, pos.y + dy) {
+                return true;
+            }
+        }
+        for (dx, dy) in KING_STEPS {
+            if let Some(Piece::King(_)) = attacker(pos.x + dx, pos.y + dy) {
+                return true;
+            }
+        }
+        for dx in [-1, 1] {
+            if let Some(Piece::Pawn(_)) = attacker(pos.x + dx, pos.y - forward(by)) {
+                return true;
+            }
+        }
+
+        for (directions, straight) in [(ROOK_DIRECTIONS, true), (BISHOP_DIRECTIONS, false)] {
+            for (dx, dy) in directions {
+                let (mut x, mut y) = (pos.x + dx, pos.y + dy);
+                while on_board(x, y) {
+                    if let Some(p) = self.get_piece(Vec2::new(x, y)) {
+                        let slides = match p {
+                            Piece::Queen(_) => true,
+                            Piece::Rook(_) => straight,
+                            Piece::Bishop(_) => !straight,
+                            _ => false,
+                        };
+                        if slides && p.get_base().is_camp(by) {
+                            return true;
+                        }
+                        break;
+                    }
+                    x += dx;
+                    y += dy;
+                }
+            }
+        }
+        false
+    }
+}
+
+impl Game {
+    // every legal move of the side to move, a pawn reaching the last rank
+    // gives one cmd per piece it can promote to
+    pub fn valid_cmds(&self) -> Vec<Cmd> {
+        let mut cmds = Vec::new();
+        if self.stage.result.is_some() {
+            return cmds;
+        }
+
+        let turn = self.stage.turn;
+        for piece in self.board.get_piece_of_camp(turn) {
+            let from = piece.get_base().pos;
+            for to in self.pseudo_legal_targets(&piece) {
+                // the rules of the piece still have the last word
+                let mut board = self.board.clone();
+                if piece.deal_move(to, &mut board, &self.stage).is_err() {
+                    continue;
+                }
+                let in_check = board
+                    .get_king_of_camp_opt(turn)
+                    .is_some_and(|k| board.is_attacked(k.base.pos, turn.opposite()));
+                if in_check {
+                    continue;
+                }
+
+                let promoting = matches!(piece, Piece::Pawn(_)) && (to.y == 0 || to.y == 7);
+                if promoting {
+                    for kind in PROMOTIONS {
+                        cmds.push(Cmd::Move(MoveCmd::new(from, to).with_promotion(kind)));
+                    }
+                } else {
+                    cmds.push(Cmd::Move(MoveCmd::new(from, to)));
+                }
+            }
+        }
+        cmds
+    }
+
+    // the squares the piece may move to, ignoring the safety of its own king
+    fn pseudo_legal_targets(&self, piece: &Piece) -> Vec<Vec2> {
+        let base = piece.get_base();
+        let from = base.pos;
+        let mut targets = Vec::new();
+
+        let empty =
+            |x: i32, y: i32| on_board(x, y) && self.board.get_piece(Vec2::new(x, y)).is_none();
+        let enemy = |x: i32, y: i32| {
+            on_board(x, y)
+                && self
+                    .board
+                    .get_piece(Vec2::new(x, y))
+                    .is_some_and(|p| !p.get_base().is_camp(base.camp))
+        };
+
+        let steps: &[(i32, i32)] = match piece {
+            Piece::Pawn(p) => {
+                let dy = forward(base.camp);
+                if empty(from.x, from.y + dy) {
+                    targets.push(Vec2::new(from.x, from.y + dy));
+                    if !p.moved && empty(from.x, from.y + 2 * dy) {
+                        targets.push(Vec2::new(from.x, from.y + 2 * dy));
+                    }
+                }
+                for dx in [-1, 1] {
+                    let to = Vec2::new(from.x + dx, from.y + dy);
+                    if enemy(to.x, to.y) || self.stage.en_passant == Some(to) {
+                        targets.push(to);
+                    }
+                }
+                return targets;
+            }
+            Piece::Knight(_) => &KNIGHT_STEPS,
+            Piece::King(_) => &KING_STEPS,
+            _ => &[],
+        };
+        for (dx, dy) in steps {
+            let (x, y) = (from.x + dx, from.y + dy);
+            if empty(x, y) || enemy(x, y) {
+                targets.push(Vec2::new(x, y));
+            }
+        }
+
+        let directions: &[(i32, i32)] = match piece {
+            Piece::Rook(_) => &ROOK_DIRECTIONS,
+            Piece::Bishop(_) => &BISHOP_DIRECTIONS,
+            // the king steps in all eight directions
+            Piece::Queen(_) => &KING_STEPS,
+            _ => &[],
+        };
+        for (dx, dy) in directions {
+            let (mut x, mut y) = (from.x + dx, from.y + dy);
+            while empty(x, y) {
+                targets.push(Vec2::new(x, y));
+                x += dx;
+                y += dy;
+            }
+            if enemy(x, y) {
+                targets.push(Vec2::new(x, y));
+            }
+        }
+
+        if let Piece::King(k) = piece {
+            for to_x in [2, 6] {
+                let to = Vec2::new(to_x, from.y);
+                if k.is_castling(to, &self.board).is_err() {
+                    continue;
+                }
+                // the king may not castle out of, through or into check,
+                // landing in check is refused with the other moves
+                let attacked = |pos: Vec2| self.board.is_attacked(pos, base.camp.opposite());
+                if !attacked(from) && !Vec2::between(from, to).into_iter().any(attacked) {
+                    targets.push(to);
+                }
+            }
+        }
+        targets
+    }
+
+    // the former generator, every from/to pair is tried on a copy of the game,
+    // kept as the reference for the tests and the benchmark
+    #[doc(hidden)]
+    pub fn valid_cmds_brute_force(&self) -> Vec<Cmd> {
+        let mut cmds = Vec::new();
+
+        for from in board_cells() {
+            for to in board_cells() {
+                let mut game_copy = self.clone();
+                if game_copy.deal_move(from, to).is_err() {
+                    continue;
+                }
+
+                // the king is safe when no opposite piece can move onto it
+                let king = game_copy.board.get_king_of_camp_opt(self.stage.turn);
+                game_copy.stage.change_turn();
+                let in_check = king.is_some_and(|king| {
+                    game_copy
+                        .board
+                        .get_piece_of_camp(self.stage.turn.opposite())
+                        .iter()
+                        .any(|p| {
+                            game_copy
+                                .clone()
+                                .deal_move(p.get_base().pos, king.base.pos)
+                                .is_ok()
+                        })
+                });
+                if in_check {
+                    continue;
+                }
+
+                let promoting = matches!(game_copy.board.get_piece(to), Some(Piece::Pawn(_)))
+                    && (to.y == 0 || to.y == 7);
+                if promoting {
+                    for kind in PROMOTIONS {
+                        cmds.push(Cmd::Move(MoveCmd::new(from, to).with_promotion(kind)));
+                    }
+                } else {
+                    cmds.push(Cmd::Move(MoveCmd::new(from, to)));
+                }
+            }
+        }
+        cmds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cmd, Game, START_FEN};
+
+    fn sorted(cmds: Vec<Cmd>) -> Vec<String> {
+        let mut cmds: Vec<String> = cmds.iter().map(|c| format!("{:?}", c)).collect();
+        cmds.sort();
+        cmds
+    }
+
+    #[test]
+    fn same_moves_as_brute_force() {
+        let fens = [
+            START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1",
+            "4k3/8/8/8/8/8/4q3/4K3 w - - 0 1",
+        ];
+        for fen in fens {
+            let game = Game::from_fen(fen).unwrap();
+            assert_eq!(
+                sorted(game.valid_cmds()),
+                sorted(game.valid_cmds_brute_force()),
+                "{}",
+                fen
+            );
+        }
+    }
+
+    #[test]
+    fn no_moves_when_the_game_is_over() {
+        // fool's mate
+        let game = Game::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3")
+            .unwrap();
+        assert!(game.valid_cmds().is_empty());
+        assert!(game.result().is_some());
+    }
+}
@@ -0,0 +1,364 @@
+use crate::{
+    abs, ChessError, Cmd, Game, GameResult, MoveCmd, Piece, PieceKind, ResultReason, Vec2,
+};
+
+// "O-O" when the king castles with the rook 3 files away, "O-O-O" otherwise
+fn castling_san(from: Vec2, to: Vec2) -> &'static str {
+    let rook_x = if to.x > from.x { 7 } else { 0 };
+    if abs(rook_x - from.x) == 3 {
+        "O-O"
+    } else {
+        "O-O-O"
+    }
+}
+
+fn is_castling(piece: &Piece, m: &MoveCmd) -> bool {
+    matches!(piece, Piece::King(_)) && abs(m.to.x - m.from.x) >= 2
+}
+
+fn as_move(cmd: &Cmd) -> Result<&MoveCmd, ChessError> {
+    match cmd {
+        Cmd::Move(m) => Ok(m),
+        _ => Err(ChessError::NotAMove),
+    }
+}
+
+fn parse_promotion(c: char) -> Option<PieceKind> {
+    PieceKind::from_letter(c).filter(|k| k.is_promotion())
+}
+
+impl Game {
+    // the cmd of a move in standard algebraic notation, such as "Nf3", "exd5", "O-O" or "e8=Q+"
+    pub fn parse_san(&self, san: &str) -> Result<Cmd, ChessError> {
+        Ok(Cmd::Move(self.parse_san_move(san)?))
+    }
+
+    // the standard algebraic notation of a move cmd, with the check or checkmate suffix
+    pub fn to_san(&self, cmd: &Cmd) -> Result<String, ChessError> {
+        self.san_of(as_move(cmd)?)
+    }
+
+    // the cmd of a move in UCI long algebraic notation, such as "e2e4" or "e7e8q"
+    pub fn parse_uci(&self, uci: &str) -> Result<Cmd, ChessError> {
+        let err = ChessError::UnparsableMove;
+        let s = uci.trim();
+        if !s.is_ascii() || (s.len() != 4 && s.len() != 5) {
+            return Err(err);
+        }
+        let from = Vec2::from_square(&s[0..2]).ok_or(err)?;
+        let to = Vec2::from_square(&s[2..4]).ok_or(err)?;
+        let mut m = MoveCmd::new(from, to);
+        if let Some(c) = s[4..].chars().next() {
+            m = m.with_promotion(parse_promotion(c).ok_or(err)?);
+        }
+
+        let cmd = Cmd::Move(m);
+        if !self.valid_cmds().contains(&cmd) {
+            return Err(ChessError::IllegalMove);
+        }
+        Ok(cmd)
+    }
+
+    // the UCI long algebraic notation of a move cmd
+    pub fn to_uci(&self, cmd: &Cmd) -> Result<String, ChessError> {
+        let m = as_move(cmd)?;
+        let mut uci = format!("{}{}", m.from.to_square(), m.to.to_square());
+        if let Some(kind) = m.promotion {
+            uci.push(kind.letter().to_ascii_lowercase());
+        }
+        Ok(uci)
+    }
+
+    // the standard algebraic notation of a legal move in the current position
+    pub(crate) fn san_of(&self, m: &MoveCmd) -> Result<String, ChessError> {
+        let piece = match self.board.get_piece(m.from) {
+            Some(p) => p,
+            None => return Err(ChessError::NoPieceAtSource),
+        };
+
+        let legal = self.valid_cmds();
+        if !legal.contains(&Cmd::Move(m.clone())) {
+            return Err(ChessError::IllegalMove);
+        }
+
+        let mut san = String::new();
+        if is_castling(&piece, m) {
+            san.push_str(castling_san(m.from, m.to));
+        } else {
+            let dest = m.to.to_square();
+
+            if let Piece::Pawn(_) = piece {
+                if m.from.x != m.to.x {
+                    san.push(m.from.to_square().remove(0));
+                    san.push('x');
+                }
+                san.push_str(&dest);
+                if let Some(kind) = m.promotion {
+                    san.push('=');
+                    san.push(kind.letter());
+                }
+            } else {
+                san.push(piece.kind().letter());
+                san.push_str(&self.disambiguation(&piece, m, &legal));
+                if self.board.get_piece(m.to).is_some() {
+                    san.push('x');
+                }
+                san.push_str(&dest);
+            }
+        }
+
+        let mut after = self.clone();
+        after.exec_cmd(&Cmd::Move(m.clone()))?;
+        match after.result() {
+            Some(GameResult::WhiteWins(ResultReason::Checkmate))
+            | Some(GameResult::BlackWins(ResultReason::Checkmate)) => san.push('#'),
+            _ => {
+                if after.after_check_king_dangerous().is_err() {
+                    san.push('+');
+                }
+            }
+        }
+        Ok(san)
+    }
+
+    // the file, rank or square of the moving piece, needed when another
+    // piece of the same kind can reach the same square
+    fn disambiguation(&self, piece: &Piece, m: &MoveCmd, legal: &[Cmd]) -> String {
+        let mut others = Vec::new();
+        for c in legal {
+            if let Cmd::Move(x) = c {
+                if x.to != m.to || x.from == m.from {
+                    continue;
+                }
+                if let Some(p) = self.board.get_piece(x.from) {
+                    if p.symbol() == piece.symbol() {
+                        others.push(x.from);
+                    }
+                }
+            }
+        }
+
+        let square = m.from.to_square();
+        if others.is_empty() {
+            String::new()
+        } else if others.iter().all(|pos| pos.x != m.from.x) {
+            square[..1].to_string()
+        } else if others.iter().all(|pos| pos.y != m.from.y) {
+            square[1..].to_string()
+        } else {
+            square
+        }
+    }
+
+    // the move described by a SAN string, check and annotation suffixes are ignored
+    pub(crate) fn parse_san_move(&self, san: &str) -> Result<MoveCmd, ChessError> {
+        let err = ChessError::UnparsableMove;
+        let s = san.trim().trim_end_matches(|c| "+#!?".contains(c));
+        if !s.is_ascii() || s.len() < 2 {
+            return Err(err);
+        }
+
+        let legal = self.valid_cmds();
+        let moves = legal.iter().filter_map(|c| match c {
+            Cmd::Move(m) => Some(m),
+            _ => None,
+        });
+
+        if s == "O-O" || s == "0-0" || s == "O-O-O" || s == "0-0-0" {
+            let wanted = s.replace('0', "O");
+            for m in moves {
+                let piece = self.board.get_piece(m.from).unwrap();
+                if is_castling(&piece, m) && castling_san(m.from, m.to) == wanted {
+                    return Ok(m.clone());
+                }
+            }
+            return Err(ChessError::IllegalMove);
+        }
+
+        let (body, promotion) = match s.find('=') {
+            Some(i) => (&s[..i], Some(&s[i + 1..])),
+            None => {
+                let last = s.chars().last().unwrap();
+                if "QRBN".contains(last) && s.len() > 2 {
+                    (&s[..s.len() - 1], Some(&s[s.len() - 1..]))
+                } else {
+                    (s, None)
+                }
+            }
+        };
+        let promotion = match promotion {
+            Some(p) => {
+                let mut chars = p.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(parse_promotion(c).ok_or(err)?),
+                    _ => return Err(err),
+                }
+            }
+            None => None,
+        };
+
+        if body.len() < 2 {
+            return Err(err);
+        }
+        let to = Vec2::from_square(&body[body.len() - 2..]).ok_or(err)?;
+        let mut prefix = &body[..body.len() - 2];
+
+        let mut kind = 'P';
+        if let Some(c) = prefix.chars().next() {
+            if "KQRBN".contains(c) {
+                kind = c;
+                prefix = &prefix[1..];
+            }
+        }
+
+        let mut from_x = None;
+        let mut from_y = None;
+        for c in prefix.chars() {
+            match c {
+                'a'..='h' => from_x = Some(c as i32 - 'a' as i32),
+                '1'..='8' => from_y = Some(c as i32 - '1' as i32),
+                'x' => {}
+                _ => return Err(err),
+            }
+        }
+
+        let mut found: Option<&MoveCmd> = None;
+        for m in moves {
+            let piece = self.board.get_piece(m.from).unwrap();
+            if m.to != to || m.promotion != promotion || piece.kind().letter() != kind {
+                continue;
+            }
+            if from_x.is_some_and(|x| x != m.from.x) || from_y.is_some_and(|y| y != m.from.y) {
+                continue;
+            }
+            if found.is_some() {
+                return Err(ChessError::AmbiguousMove);
+            }
+            found = Some(m);
+        }
+
+        found.cloned().ok_or(ChessError::IllegalMove)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cmd, Game, MoveCmd, PieceKind, Vec2};
+
+    fn mv(from: &str, to: &str) -> Cmd {
+        Cmd::Move(MoveCmd::new(
+            Vec2::from_square(from).unwrap(),
+            Vec2::from_square(to).unwrap(),
+        ))
+    }
+
+    fn san(fen: &str, from: &str, to: &str) -> String {
+        Game::from_fen(fen).unwrap().to_san(&mv(from, to)).unwrap()
+    }
+
+    #[test]
+    fn pieces_pawns_and_captures() {
+        let game = Game::new();
+        assert_eq!(game.to_san(&mv("g1", "f3")).unwrap(), "Nf3");
+        assert_eq!(game.to_san(&mv("e2", "e4")).unwrap(), "e4");
+        assert!(game.to_san(&mv("e2", "e5")).is_err());
+
+        let fen = "4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1";
+        assert_eq!(san(fen, "e4", "d5"), "exd5");
+    }
+
+    #[test]
+    fn disambiguation() {
+        let fen = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
+        assert_eq!(san(fen, "b1", "d2"), "Nbd2");
+        assert_eq!(san(fen, "f1", "d2"), "Nfd2");
+
+        let fen = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(san(fen, "a1", "a3"), "R1a3");
+        assert_eq!(san(fen, "a5", "a3"), "R5a3");
+
+        let fen = "4k3/8/8/8/8/Q1Q5/8/Q1Q1K3 w - - 0 1";
+        assert_eq!(san(fen, "a1", "b2"), "Qa1b2");
+    }
+
+    #[test]
+    fn check_mate_and_castling() {
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(san(fen, "a1", "a8"), "Ra8+");
+
+        let fen = "6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(san(fen, "a1", "a8"), "Ra8#");
+
+        let fen = "4k3/8/8/8/8/8/8/4K2R w K - 0 1";
+        assert_eq!(san(fen, "e1", "g1"), "O-O");
+        let game = Game::from_fen(fen).unwrap();
+        assert_eq!(
+            game.to_uci(&game.parse_san("O-O").unwrap()).unwrap(),
+            "e1g1"
+        );
+
+        let fen = "r3k3/8/8/8/8/8/8/4K3 b q - 0 1";
+        assert_eq!(san(fen, "e8", "c8"), "O-O-O");
+        let mut game = Game::from_fen(fen).unwrap();
+        let cmd = game.parse_san("O-O-O").unwrap();
+        assert_eq!(game.to_uci(&cmd).unwrap(), "e8c8");
+        game.exec_cmd(&cmd).unwrap();
+        assert_eq!(game.to_fen(), "2kr4/8/8/8/8/8/8/4K3 w - - 1 2");
+    }
+
+    #[test]
+    fn promotion() {
+        let game = Game::from_fen("3k4/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let cmd = game.parse_san("b8=Q+").unwrap();
+        assert_eq!(
+            cmd,
+            Cmd::Move(
+                MoveCmd::new(Vec2::new(1, 6), Vec2::new(1, 7)).with_promotion(PieceKind::Queen)
+            )
+        );
+        assert_eq!(game.to_san(&cmd).unwrap(), "b8=Q+");
+        assert_eq!(game.to_uci(&cmd).unwrap(), "b7b8q");
+
+        let cmd = game.parse_uci("b7b8r").unwrap();
+        assert_eq!(game.to_san(&cmd).unwrap(), "b8=R+");
+        assert_eq!(
+            game.to_uci(&game.parse_san("b8N").unwrap()).unwrap(),
+            "b7b8n"
+        );
+
+        assert!(game.parse_san("b8").is_err());
+        assert!(game.parse_san("b8=K").is_err());
+        assert!(game.parse_uci("b7b8").is_err());
+        assert!(game.to_san(&mv("b7", "b8")).is_err());
+    }
+
+    #[test]
+    fn parse_san() {
+        let game = Game::from_fen("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert!(game.parse_san("Ra3").is_err());
+        assert!(game.parse_san("R1a3").is_ok());
+        assert!(game.parse_san("Ra1a3").is_ok());
+        assert!(game.parse_san("Rb9").is_err());
+        assert!(game.parse_san("Qd2").is_err());
+
+        let game = Game::new();
+        let cmd = game.parse_san("Nf3!?").unwrap();
+        assert_eq!(game.to_uci(&cmd).unwrap(), "g1f3");
+    }
+
+    #[test]
+    fn uci() {
+        let mut game = Game::new();
+        let cmd = game.parse_uci("e2e4").unwrap();
+        assert_eq!(game.to_san(&cmd).unwrap(), "e4");
+        game.exec_cmd(&cmd).unwrap();
+
+        assert!(game.parse_uci("e2e4").is_err());
+        assert!(game.parse_uci("e7e5q").is_err());
+        assert!(game.parse_uci("e7").is_err());
+        assert_eq!(
+            game.to_uci(&game.parse_uci("e7e5").unwrap()).unwrap(),
+            "e7e5"
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{Cmd, Game, MoveCmd, Piece};
+
+impl Game {
+    // the number of move sequences of the given length from the current position,
+    // draws by repetition, the fifty-move rule or dead positions are not counted
+    // as game ends, like every published perft result
+    pub fn perft(&self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let cmds = self.valid_cmds();
+        if depth == 1 {
+            return cmds.len() as u64;
+        }
+
+        let mut nodes = 0;
+        for c in cmds {
+            if let Cmd::Move(m) = c {
+                let mut game = self.clone();
+                game.make_move(&m);
+                nodes += game.perft(depth - 1);
+            }
+        }
+        nodes
+    }
+
+    // the perft of the position after each legal move, keyed by the move in UCI
+    // notation; comparing it with another engine locates a wrong move generation
+    pub fn divide(&self, depth: u32) -> Vec<(String, u64)> {
+        let mut moves = Vec::new();
+        if depth == 0 {
+            return moves;
+        }
+
+        for c in self.valid_cmds() {
+            if let Cmd::Move(m) = &c {
+                let mut game = self.clone();
+                game.make_move(m);
+                moves.push((self.to_uci(&c).unwrap(), game.perft(depth - 1)));
+            }
+        }
+        moves.sort();
+        moves
+    }
+
+    // plays a move of valid_cmds on the board and the stage only, without
+    // the record, the repetitions or the result of exec_cmd: the fast path
+    // of perft and of a search, which look for the game ends themselves
+    pub fn make_move(&mut self, m: &MoveCmd) {
+        let pieces_count = self.board.piece_count();
+        let pawn_moved = matches!(self.board.get_piece(m.from), Some(Piece::Pawn(_)));
+
+        self.exec_cmd_pre(&Cmd::Move(m.clone())).unwrap();
+        let captured = self.board.piece_count() < pieces_count;
+        self.advance_stage(m, pawn_moved, captured);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Game, START_FEN};
+
+    // the node counts published on the chess programming wiki
+    const SUITE: [(&str, &[u64]); 6] = [
+        (START_FEN, &[20, 400, 8902, 197281]),
+        // "Kiwipete", castling, en passant and promotions everywhere
+        (
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &[48, 2039, 97862],
+        ),
+        // en passant captures which would expose the king
+        (
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            &[14, 191, 2812, 43238],
+        ),
+        (
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[6, 264, 9467],
+        ),
+        (
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            &[44, 1486, 62379],
+        ),
+        (
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            &[46, 2079, 89890],
+        ),
+    ];
+
+    #[test]
+    fn perft_suite() {
+        for (fen, counts) in SUITE {
+            let game = Game::from_fen(fen).unwrap();
+            for (depth, count) in counts.iter().enumerate() {
+                assert_eq!(
+                    game.perft(depth as u32 + 1),
+                    *count,
+                    "{} depth {}",
+                    fen,
+                    depth + 1
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn divide_sums_to_perft() {
+        let game = Game::from_fen(SUITE[1].0).unwrap();
+        let moves = game.divide(2);
+        assert_eq!(moves.len(), 48);
+        assert_eq!(moves.iter().map(|m| m.1).sum::<u64>(), 2039);
+        // O-O, then O-O-O
+        assert!(moves.contains(&("e1g1".to_string(), 43)));
+        assert!(moves.contains(&("e1c1".to_string(), 43)));
+        assert_eq!(game.perft(0), 1);
+    }
+}
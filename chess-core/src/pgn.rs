@@ -0,0 +1,371 @@
+use crate::{Camp, Cmd, FenError, Game, GameResult, ResultReason, START_FEN};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgnError {
+    Syntax(String),
+    Fen(FenError),
+    // ply counts from 1 for the first move in the movetext
+    IllegalMove { ply: usize, san: String },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::Syntax(s) => write!(f, "bad PGN: {}", s),
+            PgnError::Fen(e) => write!(f, "bad FEN tag: {}", e),
+            PgnError::IllegalMove { ply, san } => write!(f, "illegal move {} at ply {}", san, ply),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+const SEVEN_TAG_ROSTER: [(&str, &str); 6] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+];
+
+const LINE_WIDTH: usize = 80;
+
+fn result_token(result: Option<GameResult>) -> &'static str {
+    match result {
+        Some(GameResult::WhiteWins(_)) => "1-0",
+        Some(GameResult::BlackWins(_)) => "0-1",
+        Some(GameResult::Draw(_)) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+fn is_result_token(s: &str) -> bool {
+    s == "1-0" || s == "0-1" || s == "1/2-1/2" || s == "*"
+}
+
+impl Game {
+    // the game as PGN, the given tags override the seven tag roster defaults,
+    // the Result tag always follows the game
+    pub fn to_pgn(&self, tags: &[(&str, &str)]) -> String {
+        let result = result_token(self.stage.result);
+
+        let mut all_tags: Vec<(&str, &str)> = Vec::new();
+        for (name, default) in SEVEN_TAG_ROSTER {
+            let value = tags.iter().find(|t| t.0 == name).map_or(default, |t| t.1);
+            all_tags.push((name, value));
+        }
+        all_tags.push(("Result", result));
+        if self.start_fen != START_FEN {
+            all_tags.push(("SetUp", "1"));
+            all_tags.push(("FEN", &self.start_fen));
+        }
+        for tag in tags {
+            if !all_tags.iter().any(|t| t.0 == tag.0) {
+                all_tags.push(*tag);
+            }
+        }
+
+        let mut pgn = String::new();
+        for (name, value) in all_tags {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, value));
+        }
+        pgn.push('\n');
+
+        let mut line = String::new();
+        for token in self.movetext().into_iter().chain([result.to_string()]) {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+        pgn
+    }
+
+    // replays the history from the start position, one token per move number and SAN
+    fn movetext(&self) -> Vec<String> {
+        let mut game = Game::from_fen(&self.start_fen).unwrap();
+        let mut tokens = Vec::new();
+
+        for c in &self.cmds() {
+            let m = match c {
+                Cmd::Move(m) => m,
+                _ => continue,
+            };
+
+            if game.stage.turn == Camp::White {
+                tokens.push(format!("{}.", game.stage.fullmove_number));
+            } else if tokens.is_empty() {
+                tokens.push(format!("{}...", game.stage.fullmove_number));
+            }
+            // the history only holds accepted cmds
+            tokens.push(game.san_of(m).unwrap());
+            game.exec_cmd(c).unwrap();
+        }
+        tokens
+    }
+
+    // replays the first game of a PGN, every move is validated by exec_cmd
+    pub fn from_pgn(pgn: &str) -> Result<Game, PgnError> {
+        let (tags, movetext) = parse_tags(pgn)?;
+        let tag = |name: &str| tags.iter().find(|t| t.0 == name).map(|t| t.1.clone());
+
+        let mut game = match tag("FEN") {
+            Some(fen) => Game::from_fen(&fen).map_err(PgnError::Fen)?,
+            None => Game::new(),
+        };
+
+        let mut result = tag("Result");
+        for (i, token) in movetext_tokens(movetext)?.into_iter().enumerate() {
+            if is_result_token(&token) {
+                result = Some(token);
+                break;
+            }
+
+            let ply = i + 1;
+            let illegal = || PgnError::IllegalMove {
+                ply,
+                san: token.clone(),
+            };
+            let cmd = game.parse_san(&token).map_err(|_| illegal())?;
+            game.exec_cmd(&cmd).map_err(|_| illegal())?;
+        }
+
+        if let Some(result) = result {
+            game.apply_pgn_result(&result);
+        }
+        Ok(game)
+    }
+
+    // a result the moves do not explain came from a resignation or an agreed draw
+    fn apply_pgn_result(&mut self, result: &str) {
+        if self.stage.result.is_some() {
+            return;
+        }
+        match result {
+            "1-0" => self.exec_cmd(&Cmd::Resign(Camp::Black)).unwrap(),
+            "0-1" => self.exec_cmd(&Cmd::Resign(Camp::White)).unwrap(),
+            "1/2-1/2" => {
+                if self.claimable_draw().is_some() {
+                    self.exec_cmd(&Cmd::ClaimDraw).unwrap();
+                } else {
+                    self.stage.result = Some(GameResult::Draw(ResultReason::Agreement));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+type Tags = Vec<(String, String)>;
+
+// the tag pairs and the rest of the text
+fn parse_tags(pgn: &str) -> Result<(Tags, &str), PgnError> {
+    let mut tags = Vec::new();
+    let mut rest = pgn.trim_start();
+
+    while rest.starts_with('[') {
+        let err = || PgnError::Syntax(format!("bad tag pair: {}", rest.lines().next().unwrap()));
+
+        let body = &rest[1..];
+        let name_end = body.find(|c: char| c.is_whitespace()).ok_or_else(err)?;
+        let name = body[..name_end].to_string();
+
+        let mut chars = body[name_end..].char_indices();
+        let mut value = String::new();
+        let mut opened = false;
+        let mut end = None;
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' if !opened => opened = true,
+                '"' => {
+                    end = Some(name_end + i + 1);
+                    break;
+                }
+                '\\' if opened => {
+                    if let Some((_, escaped)) = chars.next() {
+                        value.push(escaped);
+                    }
+                }
+                c if opened => value.push(c),
+                c if c.is_whitespace() => {}
+                _ => return Err(err()),
+            }
+        }
+
+        let after_value = body[end.ok_or_else(err)?..].trim_start();
+        if !after_value.starts_with(']') {
+            return Err(err());
+        }
+        tags.push((name, value));
+        rest = after_value[1..].trim_start();
+    }
+    Ok((tags, rest))
+}
+
+// the SAN moves and the result, without move numbers, comments, NAGs and variations
+fn movetext_tokens(movetext: &str) -> Result<Vec<String>, PgnError> {
+    let mut tokens = Vec::new();
+    let mut chars = movetext.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                if !chars.by_ref().any(|c| c == '}') {
+                    return Err(PgnError::Syntax("unterminated comment".to_string()));
+                }
+            }
+            ';' => while chars.next().is_some_and(|c| c != '\n') {},
+            '(' => {
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next() {
+                        Some('(') => depth += 1,
+                        Some(')') => depth -= 1,
+                        Some('{') => {
+                            if !chars.by_ref().any(|c| c == '}') {
+                                return Err(PgnError::Syntax("unterminated comment".to_string()));
+                            }
+                        }
+                        Some(_) => {}
+                        None => return Err(PgnError::Syntax("unterminated variation".to_string())),
+                    }
+                }
+            }
+            '$' => while chars.next_if(|c| c.is_ascii_digit()).is_some() {},
+            c if c.is_whitespace() => {}
+            c => {
+                let mut token = c.to_string();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !"{}();$".contains(*c))
+                {
+                    token.push(c);
+                }
+
+                if is_result_token(&token) {
+                    tokens.push(token);
+                    continue;
+                }
+                // move numbers such as "12." or "12..." may stick to the move,
+                // digits without a dot are the move itself, as in "0-0"
+                let digits = token.trim_start_matches(|c: char| c.is_ascii_digit());
+                let san = if digits.len() < token.len() && digits.starts_with('.') {
+                    digits.trim_start_matches('.')
+                } else {
+                    &token
+                };
+                if !san.is_empty() {
+                    tokens.push(san.to_string());
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Camp, Game, GameResult, PgnError, ResultReason};
+
+    const OPENING: &str = "1. e4 d5 2. exd5 Nf6 3. Nc3 Nxd5 4. Nf3 Nb4 5. a3 *";
+
+    #[test]
+    fn export() {
+        let game = Game::from_pgn(OPENING).unwrap();
+        let pgn = game.to_pgn(&[("White", "alice"), ("Black", "bob"), ("Annotator", "me")]);
+        assert_eq!(
+            pgn,
+            "[Event \"?\"]\n\
+             [Site \"?\"]\n\
+             [Date \"????.??.??\"]\n\
+             [Round \"?\"]\n\
+             [White \"alice\"]\n\
+             [Black \"bob\"]\n\
+             [Result \"*\"]\n\
+             [Annotator \"me\"]\n\
+             \n\
+             1. e4 d5 2. exd5 Nf6 3. Nc3 Nxd5 4. Nf3 Nb4 5. a3 *\n"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let game = Game::from_pgn(OPENING).unwrap();
+        let pgn = game.to_pgn(&[]);
+        let again = Game::from_pgn(&pgn).unwrap();
+        assert_eq!(again.to_fen(), game.to_fen());
+        assert_eq!(again.to_pgn(&[]), pgn);
+    }
+
+    #[test]
+    fn setup_position_checks_and_mate() {
+        let fen = "6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1";
+        let pgn = format!("[FEN \"{}\"]\n\n1. Ra8# 1-0", fen);
+        let game = Game::from_pgn(&pgn).unwrap();
+        assert_eq!(
+            game.result(),
+            Some(GameResult::WhiteWins(ResultReason::Checkmate))
+        );
+
+        let exported = game.to_pgn(&[]);
+        assert!(exported.contains("[SetUp \"1\"]\n[FEN \"6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1\"]"));
+        assert!(exported.ends_with("\n1. Ra8# 1-0\n"));
+
+        let game =
+            Game::from_pgn("[FEN \"4k3/8/8/8/8/8/8/R3K3 b - - 0 1\"]\n1... Kd7 2. Ra7+ *").unwrap();
+        assert!(game.to_pgn(&[]).ends_with("\n1... Kd7 2. Ra7+ *\n"));
+    }
+
+    #[test]
+    fn comments_variations_and_nags() {
+        let pgn = "[Event \"test\"]\n[White \"a \\\"quoted\\\" name\"]\n\n\
+                   1. e4 {best by test} e5 $1 (1... c5 2. Nf3 (2. c3)) 2.Nf3 ; rest of line\n\
+                   Nc6 *";
+        let game = Game::from_pgn(pgn).unwrap();
+        assert_eq!(
+            game.to_fen(),
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"
+        );
+
+        // castling written with zeros
+        let pgn = "1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. 0-0 d6 5. d3 Bg4 6. Nc3 Qd7 \
+                   7. Be3 0-0-0 *";
+        let game = Game::from_pgn(pgn).unwrap();
+        assert_eq!(
+            game.to_fen(),
+            "2kr2nr/pppq1ppp/2np4/2b1p3/2B1P1b1/2NPBN2/PPP2PPP/R2Q1RK1 w - - 5 8"
+        );
+    }
+
+    #[test]
+    fn illegal_move_reports_its_ply() {
+        let err = Game::from_pgn("1. e4 e5 2. Nf3 Ke6 *").unwrap_err();
+        assert_eq!(
+            err,
+            PgnError::IllegalMove {
+                ply: 4,
+                san: "Ke6".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn resignation_from_result() {
+        let game = Game::from_pgn("1. e4 e5 0-1").unwrap();
+        assert_eq!(
+            game.result(),
+            Some(GameResult::BlackWins(ResultReason::Resignation))
+        );
+        assert_eq!(game.cmds().len(), 3);
+        assert!(matches!(game.cmds()[2], crate::Cmd::Resign(Camp::White)));
+        assert!(game.to_pgn(&[]).ends_with("\n1. e4 e5 0-1\n"));
+    }
+}
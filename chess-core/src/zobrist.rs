@@ -0,0 +1,183 @@
+use crate::{Camp, Game, Piece, PieceKind, Vec2};
+
+// random keys xored together into the hash of a position, generated at compile
+// time from a fixed seed so that a hash stays the same across builds
+struct Keys {
+    // per camp and kind, then per square x + 8 * y
+    pieces: [[[u64; 64]; 6]; 2],
+    black_to_move: u64,
+    // "K", "Q", "k" and "q"
+    castling: [u64; 4],
+    // per file
+    en_passant: [u64; 8],
+}
+
+const KEYS: Keys = keys(0x2545_f491_4f6c_dd1d);
+
+// splitmix64, returns the next state and its output
+const fn next(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (state, z ^ (z >> 31))
+}
+
+const fn keys(seed: u64) -> Keys {
+    let mut keys = Keys {
+        pieces: [[[0; 64]; 6]; 2],
+        black_to_move: 0,
+        castling: [0; 4],
+        en_passant: [0; 8],
+    };
+    let mut state = seed;
+    let mut key;
+
+    let mut camp = 0;
+    while camp < 2 {
+        let mut kind = 0;
+        while kind < 6 {
+            let mut square = 0;
+            while square < 64 {
+                (state, key) = next(state);
+                keys.pieces[camp][kind][square] = key;
+                square += 1;
+            }
+            kind += 1;
+        }
+        camp += 1;
+    }
+
+    (state, key) = next(state);
+    keys.black_to_move = key;
+
+    let mut i = 0;
+    while i < 4 {
+        (state, key) = next(state);
+        keys.castling[i] = key;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 8 {
+        (state, key) = next(state);
+        keys.en_passant[i] = key;
+        i += 1;
+    }
+    keys
+}
+
+// the key of a piece on the square x + 8 * y
+pub(crate) fn piece_key(kind: PieceKind, camp: Camp, square: usize) -> u64 {
+    let camp = match camp {
+        Camp::White => 0,
+        Camp::Black => 1,
+    };
+    KEYS.pieces[camp][kind as usize][square]
+}
+
+impl Game {
+    // the Zobrist hash of the position: the pieces, the side to move, the castling
+    // rights and the file of the en passant square when a pawn can take there;
+    // repeated positions have equal hashes whatever the moves leading to them
+    pub fn hash(&self) -> u64 {
+        let mut hash = self.board.hash();
+        if self.stage.turn == Camp::Black {
+            hash ^= KEYS.black_to_move;
+        }
+        for c in self.castling_rights().chars() {
+            if let Some(i) = "KQkq".find(c) {
+                hash ^= KEYS.castling[i];
+            }
+        }
+        if let Some(pos) = self.stage.en_passant {
+            if self.en_passant_capturable(pos) {
+                hash ^= KEYS.en_passant[pos.x as usize];
+            }
+        }
+        hash
+    }
+
+    // whether a pawn of the side to move stands next to the pawn which just passed
+    fn en_passant_capturable(&self, pos: Vec2) -> bool {
+        let turn = self.stage.turn;
+        let y = if turn == Camp::White {
+            pos.y - 1
+        } else {
+            pos.y + 1
+        };
+        [pos.x - 1, pos.x + 1].into_iter().any(|x| {
+            matches!(self.board.get_piece(Vec2::new(x, y)),
+                Some(Piece::Pawn(p)) if p.base.is_camp(turn))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::play_san;
+    use crate::{ChessBoard, Game};
+
+    fn hash_after(moves: &[&str]) -> u64 {
+        let mut game = Game::new();
+        play_san(&mut game, moves);
+        game.hash()
+    }
+
+    #[test]
+    fn transpositions_have_equal_hashes() {
+        let a = hash_after(&["Nf3", "Nf6", "Nc3", "Nc6"]);
+        let b = hash_after(&["Nc3", "Nc6", "Nf3", "Nf6"]);
+        assert_eq!(a, b);
+        assert_ne!(a, Game::new().hash());
+
+        let a = hash_after(&["e4", "e5", "d4", "d5"]);
+        let b = hash_after(&["d4", "d5", "e4", "e5"]);
+        assert_eq!(a, b);
+
+        // the knights went out and back, so only the side to move differs
+        assert_ne!(
+            hash_after(&["Nf3", "Nf6", "Ng1"]),
+            hash_after(&["Nf3", "Nf6", "Ng1", "Ng8"])
+        );
+        assert_eq!(
+            hash_after(&["Nf3", "Nf6", "Ng1", "Ng8"]),
+            Game::new().hash()
+        );
+    }
+
+    #[test]
+    fn incremental_hash_matches_a_fresh_one() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        for m in [
+            "a4", "bxa3", "O-O", "hxg2", "Qxf6", "gxf1=Q+", "Kxf1", "O-O-O",
+        ] {
+            play_san(&mut game, &[m]);
+            let fresh = Game::from_fen(&game.to_fen()).unwrap();
+            assert_eq!(game.hash(), fresh.hash(), "{}", game.to_fen());
+        }
+
+        let start = Game::from_fen(fen).unwrap().hash();
+        while game.can_undo() {
+            game.undo().unwrap();
+        }
+        assert_eq!(game.hash(), start);
+        assert_eq!(ChessBoard::new().hash(), 0);
+    }
+
+    #[test]
+    fn castling_and_en_passant_rights_change_the_hash() {
+        let with = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let without = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kkq - 0 1").unwrap();
+        assert_ne!(with.hash(), without.hash());
+
+        // no black pawn can take on e3, the square does not count
+        let ep = Game::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1").unwrap();
+        let no_ep = Game::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(ep.hash(), no_ep.hash());
+
+        let ep = Game::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").unwrap();
+        let no_ep = Game::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1").unwrap();
+        assert_ne!(ep.hash(), no_ep.hash());
+    }
+}
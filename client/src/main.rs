@@ -1,20 +1,38 @@
 use std::sync::{Arc, Mutex};
 
-use chess_core::{Cmd, Game, MoveCmd, Piece, PromoteCmd, Vec2};
+use chess_core::{Camp, Cmd, Game, MoveCmd, Piece, PieceKind, TimeControl, Vec2};
+use futures_util::{SinkExt, StreamExt};
 use server;
+use tokio::net::TcpStream;
 use tokio::sync::mpsc;
-use tokio::time::{sleep, Duration};
+use tokio::time::{sleep, timeout, Duration, Instant};
 use tokio::{self, join};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
 use tui::{Areas, Event, Ui};
 
 use lexer::{self, Token};
 
+// the delays between two attempts to reach a game, growing while it fails
+const RETRY_MIN: Duration = Duration::from_millis(250);
+const RETRY_MAX: Duration = Duration::from_secs(5);
+// three heartbeats of the server missed
+const SILENCE: Duration = Duration::from_secs(15);
+
 struct Client {
     connected: Arc<Mutex<bool>>,
     ui: Ui,
     game: Arc<Mutex<Game>>,
+    // the game joined on the server, if any
+    game_id: Arc<Mutex<Option<u64>>>,
+    // the clocks of the game as last sent, and when they arrived
+    clock: Arc<Mutex<Option<(server::ClockState, Instant)>>>,
+    // the piece a pawn becomes when it reaches the last rank
+    promotion: PieceKind,
     id: String,
     token: String,
+    // the colours of the pieces, from the profile of the user
+    colours: server::Colours,
 }
 //
 impl Client {
@@ -23,68 +41,155 @@ impl Client {
             connected: Arc::new(Mutex::new(false)),
             ui: Ui::new(),
             game: Arc::new(Mutex::new(Game::new())),
+            game_id: Arc::new(Mutex::new(None)),
+            clock: Arc::new(Mutex::new(None)),
+            promotion: PieceKind::Queen,
             id: String::new(),
             token: String::new(),
+            colours: server::Colours::default(),
         };
     }
 
+    // asks the id and the password until the server gives a token,
+    // "new" opens the screen creating an account
     async fn login(&mut self) {
         let mut login_state = "id_input";
+        let id_prompt = "please input id, or new to create an account";
 
-        self.ui.areas.message = "please input id".to_string();
+        self.ui.areas.message = id_prompt.to_string();
         loop {
+            self.ui.render();
             let event = self.ui.next_event(10).await;
 
             match event {
                 Event::StringInput(x) => {
-                    if login_state == "id_input" {
-                        self.id = x;
+                    if login_state == "id_input" && x.trim() == "new" {
+                        if self.create_account().await {
+                            break;
+                        }
+                        self.ui.areas.message = id_prompt.to_string();
+                    } else if login_state == "id_input" {
+                        self.id = x.trim().to_string();
                         login_state = "passport_input";
                         self.ui.areas.message = "please input password".to_string();
                     } else if login_state == "passport_input" {
-                        login_state = "logging";
                         self.ui.areas.message = "logging...".to_string();
-                        let token = login_post(self.id.clone(), x).await;
-                        if token.is_ok() {
-                            self.token = token.unwrap();
-                            self.ui.areas.message = "success".to_string();
-                            break;
-                        } else {
-                            self.ui.areas.message = token.err().unwrap().to_string();
-                            break;
+                        self.ui.render();
+                        match login_post(self.id.clone(), x).await {
+                            Ok(token) => {
+                                self.token = token;
+                                self.ui.areas.message = "success".to_string();
+                                break;
+                            }
+                            Err(e) => {
+                                login_state = "id_input";
+                                self.ui.areas.message = format!("{}, please input id again", e);
+                            }
                         }
                     }
                 }
 
+                Event::ExitSignal => panic!("you escaped!"),
+
                 _ => {}
             }
+        }
+
+        if let Ok(profile) = profile_get(&self.id).await {
+            self.colours = profile.colours;
+        }
+    }
+
+    // the account screen: asks each field of the form in turn, and again the
+    // ones the server refuses; true once registered and logged in, false when
+    // an empty id goes back to the login
+    async fn create_account(&mut self) -> bool {
+        const FIELDS: [&str; 4] = ["id", "password", "password again", "display name"];
+        let mut values = vec![String::new(); FIELDS.len()];
+        let mut errors: Vec<String> = Vec::new();
+        let mut field = 0;
 
+        loop {
+            self.ui.areas.panel = account_panel(&FIELDS, &values, field, &errors);
+            self.ui.areas.message = match field {
+                0 => "id: a letter then letters or digits, empty to go back".to_string(),
+                3 => "display name, empty for the id".to_string(),
+                _ => format!("{}: at least 6 characters", FIELDS[field]),
+            };
             self.ui.render();
+
+            let x = match self.ui.next_event(10).await {
+                Event::StringInput(x) => x,
+                Event::ExitSignal => panic!("you escaped!"),
+                _ => continue,
+            };
+            if field == 0 && x.trim().is_empty() {
+                self.ui.areas.panel.clear();
+                return false;
+            }
+            values[field] = if field == 0 { x.trim().to_string() } else { x };
+            field += 1;
+            if field == 2 && values[1] != values[2] {
+                errors = vec!["the passwords differ".to_string()];
+                field = 1;
+            }
+            if field < FIELDS.len() {
+                continue;
+            }
+
+            let req = server::RegisterRequest {
+                id: values[0].clone(),
+                password: values[1].clone(),
+                display_name: Some(values[3].clone()).filter(|n| !n.is_empty()),
+            };
+            match register_post(&req).await {
+                Ok(token) => {
+                    self.id = req.id;
+                    self.token = token;
+                    self.ui.areas.panel.clear();
+                    self.ui.areas.message = "account created".to_string();
+                    return true;
+                }
+                Err(refused) => {
+                    // back to the first field refused
+                    field = refused
+                        .iter()
+                        .filter_map(|e| match e.field.as_str() {
+                            "id" => Some(0),
+                            "password" => Some(1),
+                            "display_name" => Some(3),
+                            _ => None,
+                        })
+                        .min()
+                        .unwrap_or(0);
+                    errors = refused.into_iter().map(|e| e.message).collect();
+                }
+            }
         }
     }
 
     async fn run(&mut self) {
         let game_ref = self.game.clone();
         let connected_ref = self.connected.clone();
-        tokio::spawn(async move {
-            loop {
-                let gamestate = { game_state_post().await };
-                {
-                    if gamestate.is_ok() {
-                        *(game_ref.lock().unwrap()) = gamestate.unwrap().clone();
-                        *connected_ref.lock().unwrap() = true;
-                    } else {
-                        *connected_ref.lock().unwrap() = false;
-                    }
-                }
-
-                sleep(Duration::from_millis(30)).await;
-            }
-        });
+        let game_id_ref = self.game_id.clone();
+        let clock_ref = self.clock.clone();
+        tokio::spawn(follow_games(
+            game_ref,
+            clock_ref,
+            connected_ref,
+            game_id_ref,
+        ));
         self.ui.render();
         loop {
             {
-                let event = self.ui.next_event(10).await;
+                let mut event = self.ui.next_event(10).await;
+                // the colours belong to the user, not to the game
+                if let Event::StringInput(x) = &event {
+                    if let Some(wanted) = parse_colours_cmd(x) {
+                        self.ui.areas.message = self.save_colours(wanted).await;
+                        event = Event::TimerSignal;
+                    }
+                }
                 {
                     let noconnected_msg = "can not connect to server";
 
@@ -93,33 +198,76 @@ impl Client {
                         self.ui.areas.message = noconnected_msg.to_string();
                     }
 
-                    Self::deal_func(&mut self.ui, event, connected).await;
+                    let game = self.game.lock().unwrap().clone();
+                    Self::deal_func(
+                        &mut self.ui,
+                        event,
+                        connected,
+                        &game,
+                        &self.game_id,
+                        &self.token,
+                        &mut self.promotion,
+                    )
+                    .await;
                 }
 
                 self.ui.areas.grid_area.buffers.clear();
                 {
-                    for ele in &self.game.lock().unwrap().board.board {
+                    let game = self.game.lock().unwrap();
+                    if let Some(result) = game.result() {
+                        self.ui.areas.message = format!("game over: {}", result);
+                    }
+
+                    for p in game.board.pieces() {
+                        let base = p.get_base();
                         let temp: String;
-                        if ele.1.get_base().is_white() {
-                            temp = Ui::color1(ele.1.get_base().name.as_str())
+                        if base.is_white() {
+                            temp = Ui::named_color(base.name.as_str(), &self.colours.white)
                         } else {
-                            temp = Ui::color2(ele.1.get_base().name.as_str())
+                            temp = Ui::named_color(base.name.as_str(), &self.colours.black)
                         }
 
                         self.ui
                             .areas
                             .grid_area
                             .buffers
-                            .insert(ele.0.to_string(), temp);
+                            .insert(base.pos.to_string(), temp);
                     }
                 }
+                self.ui.areas.panel = clock_panel(&self.clock.lock().unwrap());
 
                 self.ui.render();
             }
         }
     }
 
-    async fn deal_func(ui: &mut Ui, event: Event, connected: bool) {
+    async fn save_colours(&mut self, wanted: server::Colours) -> String {
+        let update = server::ProfileUpdate {
+            colours: Some(wanted),
+            ..Default::default()
+        };
+        match profile_patch(&self.token, &update).await {
+            Ok(profile) => {
+                self.colours = profile.colours;
+                "colours saved".to_string()
+            }
+            Err(errors) => {
+                let messages: Vec<String> = errors.into_iter().map(|e| e.message).collect();
+                messages.join(", ")
+            }
+        }
+    }
+
+    async fn deal_func(
+        ui: &mut Ui,
+        event: Event,
+        connected: bool,
+        game: &Game,
+        game_id: &Mutex<Option<u64>>,
+        token: &str,
+        promotion: &mut PieceKind,
+    ) {
+        let id = *game_id.lock().unwrap();
         match event {
             Event::ExitSignal => {
                 panic!("you escaped!")
@@ -129,18 +277,35 @@ impl Client {
 
             Event::StringInput(x) => {
                 ui.areas.message.clear();
-                let p = parse_promot_cmd(x.as_str());
-                ui.areas.message.push_str(format!("{:?}", p).as_str());
-                if let Ok(piece) = p {
-                    let info = game_cmd_post(Cmd::Promote(PromoteCmd {
-                        from: Vec2::new(
-                            ui.areas.grid_area.cur_x as i32,
-                            ui.areas.grid_area.cur_y as i32,
-                        ),
-                        to: piece,
-                    })).await;
+                if x.trim() == "draw" {
+                    ui.areas.message = game_cmd_post(id, token, Cmd::ClaimDraw).await;
+                    return;
+                }
 
-                    ui.areas.message = info;
+                if let Ok(cmd) = parse_room_cmd(x.as_str()) {
+                    ui.areas.message = match cmd {
+                        RoomCmd::New(req) => games_post(req, game_id, token).await,
+                        RoomCmd::Join(id) => join_game(id, game_id, token).await,
+                        RoomCmd::List => match games_get().await {
+                            Ok(games) => games_message(&games),
+                            Err(e) => e.to_string(),
+                        },
+                    };
+                    return;
+                }
+
+                // moves can be typed as "Nf3" or "g1f3"
+                if let Ok(cmd) = game.parse_san(&x).or_else(|_| game.parse_uci(&x)) {
+                    ui.areas.message = game_cmd_post(id, token, cmd).await;
+                    return;
+                }
+
+                match parse_promot_cmd(x.as_str()) {
+                    Ok(kind) => {
+                        *promotion = kind;
+                        ui.areas.message = format!("pawns will promote to {:?}", kind);
+                    }
+                    Err(e) => ui.areas.message = e,
                 }
             }
 
@@ -156,14 +321,17 @@ impl Client {
                     ui.areas.grid_area.select_y = y;
                 } else {
                     ui.areas.grid_area.selected = false;
-                    let info = game_cmd_post(Cmd::Move(MoveCmd::new(
-                        Vec2::new(
-                            ui.areas.grid_area.select_x as i32,
-                            ui.areas.grid_area.select_y as i32,
-                        ),
-                        Vec2::new(x as i32, y as i32),
-                    )))
-                    .await;
+                    let from = Vec2::new(
+                        ui.areas.grid_area.select_x as i32,
+                        ui.areas.grid_area.select_y as i32,
+                    );
+                    let to = Vec2::new(x as i32, y as i32);
+                    let mut m = MoveCmd::new(from, to);
+                    let is_pawn = matches!(game.board.get_piece(from), Some(Piece::Pawn(_)));
+                    if is_pawn && (y == 0 || y == 7) {
+                        m = m.with_promotion(*promotion);
+                    }
+                    let info = game_cmd_post(id, token, Cmd::Move(m)).await;
                     ui.areas.message = info;
                 }
             }
@@ -171,7 +339,7 @@ impl Client {
     }
 }
 
-fn parse_promot_cmd(s: &str) -> Result<String, String> {
+fn parse_promot_cmd(s: &str) -> Result<PieceKind, String> {
     let mut l = lexer::Lexer::new();
     l.add_keyword("promote");
     l.add_keyword("queen");
@@ -186,8 +354,11 @@ fn parse_promot_cmd(s: &str) -> Result<String, String> {
                 let tobe = l.result.get(1).unwrap().clone();
                 match tobe {
                     Token::Keyword(x) => match x.as_str() {
-                        "promotion" => return Err(String::from("can not parse a PromoteCmd")),
-                        _ => return Ok(x),
+                        "queen" => return Ok(PieceKind::Queen),
+                        "rook" => return Ok(PieceKind::Rook),
+                        "bishop" => return Ok(PieceKind::Bishop),
+                        "knight" => return Ok(PieceKind::Knight),
+                        _ => {}
                     },
                     _ => {}
                 }
@@ -198,36 +369,431 @@ fn parse_promot_cmd(s: &str) -> Result<String, String> {
     Err(String::from("can not parse a PromoteCmd"))
 }
 
-async fn game_cmd_post(cmd: Cmd) -> String {
+enum RoomCmd {
+    New(server::NewGameRequest),
+    Join(u64),
+    List,
+}
+
+// a new game may end with its time control: "new fischer 5 3", "bot black 3 days 2"
+fn parse_room_cmd(s: &str) -> Result<RoomCmd, String> {
+    let words: Vec<&str> = s.split_whitespace().collect();
+    let at = words
+        .iter()
+        .position(|w| ["fischer", "bronstein", "days"].contains(w));
+    let Some(at) = at else {
+        return parse_room_words(s);
+    };
+    let clock = parse_time_control(&words[at..]);
+    match parse_room_words(&words[..at].join(" "))? {
+        RoomCmd::New(req) if clock.is_some() => {
+            Ok(RoomCmd::New(server::NewGameRequest { clock, ..req }))
+        }
+        _ => Err(String::from("can not parse a game cmd")),
+    }
+}
+
+// "fischer 5 3": 5 minutes, and 3 seconds more after each move; "bronstein 5 3"
+// gives back up to 3 seconds of each move; "days 2": 2 days for each move
+fn parse_time_control(words: &[&str]) -> Option<TimeControl> {
+    let numbers: Option<Vec<u64>> = words[1..].iter().map(|w| w.parse().ok()).collect();
+    match (words[0], numbers?.as_slice()) {
+        ("fischer", [minutes, seconds]) => Some(TimeControl::Fischer {
+            base: minutes * 60_000,
+            increment: seconds * 1000,
+        }),
+        ("bronstein", [minutes, seconds]) => Some(TimeControl::Bronstein {
+            base: minutes * 60_000,
+            delay: seconds * 1000,
+        }),
+        ("days", [days]) if *days > 0 => Some(TimeControl::Correspondence { days: *days }),
+        _ => None,
+    }
+}
+
+// "new" or "new black" for a game between two players, "bot black 3" for a game
+// against the server playing black 3 plies deep, "join 2" and "games" to list them
+fn parse_room_words(s: &str) -> Result<RoomCmd, String> {
+    let mut l = lexer::Lexer::new();
+    l.add_keyword("new");
+    l.add_keyword("bot");
+    l.add_keyword("white");
+    l.add_keyword("black");
+    l.add_keyword("join");
+    l.add_keyword("games");
+    l.tokenize(s);
+
+    let err = String::from("can not parse a game cmd");
+    match l.result.as_slice() {
+        [Token::Keyword(x)] if x == "new" => Ok(RoomCmd::New(server::NewGameRequest {
+            bot: None,
+            camp: None,
+            clock: None,
+        })),
+        [Token::Keyword(x), Token::Keyword(camp)] if x == "new" => {
+            Ok(RoomCmd::New(server::NewGameRequest {
+                bot: None,
+                camp: Some(parse_camp(camp).ok_or(err)?),
+                clock: None,
+            }))
+        }
+        [Token::Keyword(x)] if x == "games" => Ok(RoomCmd::List),
+        [Token::Keyword(x), Token::Int(id)] if x == "join" && *id > 0 => {
+            Ok(RoomCmd::Join(*id as u64))
+        }
+        [Token::Keyword(x), Token::Keyword(camp), rest @ ..] if x == "bot" => {
+            let camp = parse_camp(camp).ok_or(err.clone())?;
+            let depth = match rest {
+                [] => 3,
+                [Token::Int(d)] if *d > 0 => *d as u32,
+                _ => return Err(err),
+            };
+            Ok(RoomCmd::New(server::NewGameRequest {
+                bot: Some(server::BotConfig { camp, depth }),
+                camp: None,
+                clock: None,
+            }))
+        }
+        _ => Err(err),
+    }
+}
+
+fn parse_camp(s: &str) -> Option<Camp> {
+    match s {
+        "white" => Some(Camp::White),
+        "black" => Some(Camp::Black),
+        _ => None,
+    }
+}
+
+// "1: 12 moves, 2: 1-0, 3: 4 moves against the bot"
+fn games_message(games: &[server::GameSummary]) -> String {
+    if games.is_empty() {
+        return "no game yet, type new or bot black 3".to_string();
+    }
+    let games: Vec<String> = games
+        .iter()
+        .map(|g| {
+            let state = match g.result {
+                Some(result) => result.to_string(),
+                None => format!("{} moves", g.moves),
+            };
+            let bot = if g.bot.is_some() {
+                " against the bot"
+            } else {
+                ""
+            };
+            format!("{}: {}{}", g.id, state, bot)
+        })
+        .collect();
+    games.join(", ")
+}
+
+// "4:59", "0:09.3" in the last seconds, "47:59:59" for days
+fn format_time(ms: u64) -> String {
+    let secs = ms / 1000;
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else if secs >= 10 {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    } else {
+        format!("0:{:02}.{}", secs, ms % 1000 / 100)
+    }
+}
+
+// the clocks beside the board, black's at the top and white's at the bottom;
+// the one running counts down from the time the server sent
+fn clock_panel(clock: &Option<(server::ClockState, Instant)>) -> Vec<String> {
+    let Some((state, at)) = clock else {
+        return Vec::new();
+    };
+    let line = |camp: Camp, left: u64| {
+        let left = if state.running == Some(camp) {
+            left.saturating_sub(at.elapsed().as_millis() as u64)
+        } else {
+            left
+        };
+        let mark = if state.running == Some(camp) {
+            " <"
+        } else {
+            ""
+        };
+        format!("{:?} {}{}", camp, format_time(left), mark)
+    };
+    let mut lines = vec![String::new(); 23];
+    lines[1] = line(Camp::Black, state.black);
+    lines[22] = line(Camp::White, state.white);
+    lines
+}
+
+// the form of the account screen, the passwords hidden, an arrow on the field asked
+fn account_panel(
+    fields: &[&str],
+    values: &[String],
+    field: usize,
+    errors: &[String],
+) -> Vec<String> {
+    let mut lines = vec!["create an account".to_string(), String::new()];
+    for (i, name) in fields.iter().enumerate() {
+        let value = if i == 1 || i == 2 {
+            "*".repeat(values[i].chars().count())
+        } else {
+            values[i].clone()
+        };
+        let arrow = if i == field { "->" } else { "  " };
+        lines.push(format!("{} {:<16}{}", arrow, format!("{}:", name), value));
+    }
+    lines.push(String::new());
+    lines.extend(errors.iter().map(|e| Ui::color2(e)));
+    lines
+}
+
+// "colours blue yellow": the colours of the white and the black pieces
+fn parse_colours_cmd(s: &str) -> Option<server::Colours> {
+    match s.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["colours", white, black] => Some(server::Colours {
+            white: white.to_string(),
+            black: black.to_string(),
+        }),
+        _ => None,
+    }
+}
+
+// the messages of the errors of a 400 answer, or why there is no answer
+async fn validation_errors(res: reqwest::Result<reqwest::Response>) -> Vec<server::FieldError> {
+    let errors = match res {
+        Ok(res) => res
+            .json::<server::ValidationErrors>()
+            .await
+            .map(|v| v.errors),
+        Err(_) => return vec![server::FieldError::new("", "server connected fail")],
+    };
+    errors.unwrap_or_else(|_| vec![server::FieldError::new("", "unexpected answer")])
+}
+
+// the token of the new account
+async fn register_post(req: &server::RegisterRequest) -> Result<String, Vec<server::FieldError>> {
+    let res = reqwest::Client::new()
+        .post("http://localhost:8080/register")
+        .json(req)
+        .send()
+        .await;
+    match res {
+        Ok(res) if res.status().is_success() => {
+            let response: server::LoginResponse = res.json().await.unwrap();
+            Ok(response.token)
+        }
+        res => Err(validation_errors(res).await),
+    }
+}
+
+async fn profile_get(id: &str) -> Result<server::Profile, &'static str> {
+    let res = reqwest::get(format!("http://localhost:8080/users/{}", id)).await;
+    match res {
+        Ok(res) if res.status().is_success() => Ok(res.json().await.unwrap()),
+        Ok(_) => Err("no such user"),
+        Err(_) => Err("server connected fail"),
+    }
+}
+
+async fn profile_patch(
+    token: &str,
+    update: &server::ProfileUpdate,
+) -> Result<server::Profile, Vec<server::FieldError>> {
+    let res = reqwest::Client::new()
+        .patch("http://localhost:8080/users/me")
+        .bearer_auth(token)
+        .json(update)
+        .send()
+        .await;
+    match res {
+        Ok(res) if res.status().is_success() => Ok(res.json().await.unwrap()),
+        res => Err(validation_errors(res).await),
+    }
+}
+
+async fn games_post(
+    req: server::NewGameRequest,
+    game_id: &Mutex<Option<u64>>,
+    token: &str,
+) -> String {
+    let c = reqwest::Client::new();
+    let res = c
+        .post("http://localhost:8080/games")
+        .bearer_auth(token)
+        .json(&req)
+        .send()
+        .await;
+
+    let summary: server::GameSummary = match res {
+        Ok(res) if res.status().is_success() => res.json().await.unwrap(),
+        Ok(res) => return res.text().await.unwrap_or_default(),
+        Err(_) => return "server connected fail".to_string(),
+    };
+    *game_id.lock().unwrap() = Some(summary.id);
+    match summary.bot {
+        None => format!("new game {}", summary.id),
+        Some(bot) => format!("new game {}, the bot plays {:?}", summary.id, bot.camp),
+    }
+}
+
+async fn games_get() -> Result<Vec<server::GameSummary>, &'static str> {
+    let res = reqwest::get("http://localhost:8080/games").await;
+    match res {
+        Ok(res) => Ok(res.json().await.unwrap()),
+        Err(_) => Err("server connected fail"),
+    }
+}
+
+// takes a free seat of the game, or watches it
+async fn join_game(id: u64, game_id: &Mutex<Option<u64>>, token: &str) -> String {
+    let c = reqwest::Client::new();
+    let res = c
+        .post(format!("http://localhost:8080/games/{}/join", id))
+        .bearer_auth(token)
+        .json(&server::JoinRequest { camp: None })
+        .send()
+        .await;
+
+    let response: server::JoinResponse = match res {
+        Ok(res) if res.status().is_success() => res.json().await.unwrap(),
+        Ok(res) => return res.text().await.unwrap_or_default(),
+        Err(_) => return "server connected fail".to_string(),
+    };
+    *game_id.lock().unwrap() = Some(id);
+    match response.camp {
+        Some(camp) => format!("you play {:?} in game {}", camp, id),
+        None => format!("you watch game {}", id),
+    }
+}
+
+async fn game_cmd_post(game_id: Option<u64>, token: &str, cmd: Cmd) -> String {
+    let id = match game_id {
+        Some(id) => id,
+        None => return "no game, type new, bot black 3 or join 1".to_string(),
+    };
     let c = reqwest::Client::new();
     let res = c
-        .post("http://localhost:8080/game/cmd")
+        .post(format!("http://localhost:8080/games/{}/cmd", id))
+        .bearer_auth(token)
         .json(&cmd)
         .send()
         .await
         .unwrap();
 
-    println!("{:?}", res);
-    let s: String = res.json().await.unwrap();
-    s
+    // not logged in, or not the player of the camp
+    if !res.status().is_success() {
+        return res.text().await.unwrap_or_default();
+    }
+    let response: server::CmdResponse = res.json().await.unwrap();
+    match response.err {
+        Some(e) => e.to_string(),
+        None => "ok".to_string(),
+    }
     //println!("{:?}",game)
 }
 
-async fn game_state_post() -> Result<Game, &'static str> {
-    let c = reqwest::Client::new();
-    let res = c.post("http://localhost:8080/game/state").send().await;
+// keeps the game joined up to date with the events pushed by the server; a
+// lost connection is retried less and less often, and every new connection
+// starts with the whole state
+async fn follow_games(
+    game: Arc<Mutex<Game>>,
+    clock: Arc<Mutex<Option<(server::ClockState, Instant)>>>,
+    connected: Arc<Mutex<bool>>,
+    game_id: Arc<Mutex<Option<u64>>>,
+) {
+    let mut retry = RETRY_MIN;
+    loop {
+        let id = *game_id.lock().unwrap();
+        let Some(id) = id else {
+            // nothing to follow until a game is joined
+            *connected.lock().unwrap() = games_get().await.is_ok();
+            sleep(Duration::from_millis(500)).await;
+            continue;
+        };
 
-    if res.is_ok() {
-        let game: Game = res.unwrap().json().await.unwrap();
-        Ok(game)
-    } else {
-        Err("server connected fail")
+        match connect_async(format!("ws://localhost:8080/games/{}/ws", id)).await {
+            Ok((ws, _)) => {
+                *connected.lock().unwrap() = true;
+                retry = RETRY_MIN;
+                if !follow_game(ws, id, &game, &clock, &game_id).await {
+                    *connected.lock().unwrap() = false;
+                }
+            }
+            Err(_) => {
+                *connected.lock().unwrap() = false;
+                sleep(retry).await;
+                retry = (retry * 2).min(RETRY_MAX);
+            }
+        }
     }
+}
 
-    //println!("{:?}",game)
+// applies the events of the game id until another game is joined, which
+// returns true, or the connection is lost
+async fn follow_game(
+    mut ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    id: u64,
+    game: &Mutex<Game>,
+    clock: &Mutex<Option<(server::ClockState, Instant)>>,
+    game_id: &Mutex<Option<u64>>,
+) -> bool {
+    let mut heard = Instant::now();
+    loop {
+        if *game_id.lock().unwrap() != Some(id) {
+            return true;
+        }
+        // the server pings every few seconds, silence means the link is dead
+        if heard.elapsed() > SILENCE {
+            return false;
+        }
+        let msg = match timeout(Duration::from_millis(200), ws.next()).await {
+            Err(_) => continue,
+            Ok(Some(Ok(msg))) => msg,
+            Ok(_) => return false,
+        };
+        heard = Instant::now();
+        let event = match msg {
+            Message::Text(text) => serde_json::from_str::<server::GameEvent>(&text),
+            _ => continue,
+        };
+        let synced = match event {
+            Ok(event) => apply_event(&mut game.lock().unwrap(), &mut clock.lock().unwrap(), event),
+            Err(_) => false,
+        };
+        if !synced && ws.send(Message::Text("sync".to_string())).await.is_err() {
+            return false;
+        }
+    }
+}
+
+// false when the event does not follow the game known, which needs the
+// whole state again
+fn apply_event(
+    game: &mut Game,
+    clock: &mut Option<(server::ClockState, Instant)>,
+    event: server::GameEvent,
+) -> bool {
+    match event {
+        server::GameEvent::State(state) => {
+            *game = state.game;
+            *clock = state.clock.map(|c| (c, Instant::now()));
+            true
+        }
+        server::GameEvent::Cmd { ply, cmd, clock: c } => {
+            let known = game.history().len();
+            // already in the state sent first
+            if ply < known {
+                return true;
+            }
+            *clock = c.map(|c| (c, Instant::now()));
+            ply == known && game.exec_cmd(&cmd).is_ok()
+        }
+        server::GameEvent::Players { .. } => true,
+    }
 }
 
-async fn login_post(id: String, password: String) -> Result<String, &'static str> {
+async fn login_post(id: String, password: String) -> Result<String, String> {
     let c = reqwest::Client::new();
     let req = server::LoginRequest { id, password };
     let res = c
@@ -241,10 +807,10 @@ async fn login_post(id: String, password: String) -> Result<String, &'static str
         if response.ok {
             Ok(response.token)
         } else {
-            Err("login fail")
+            Err(response.err)
         }
     } else {
-        Err("server connected fail")
+        Err("server connected fail".to_string())
     }
 
     //println!("{:?}",game)
@@ -252,7 +818,7 @@ async fn login_post(id: String, password: String) -> Result<String, &'static str
 
 async fn example2() {
     let mut client = Client::new();
-    //client.login().await;
+    client.login().await;
     client.run().await;
 }
 
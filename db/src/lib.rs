@@ -0,0 +1,222 @@
+use lexer::Lexer;
+pub use lexer::Token;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
+where
+    P: AsRef<Path>,
+{
+    let file = File::open(filename)?;
+    Ok(io::BufReader::new(file).lines())
+}
+
+// a table kept in a text file: the names of the fields on the first line,
+// then a row per line, the first field of a row is its key
+#[derive(Debug, Clone)]
+pub struct DB {
+    head: Vec<String>,
+    tables: HashMap<String, Vec<Token>>,
+}
+
+impl DB {
+    pub fn open(filepath: &str) -> Result<DB, ()> {
+        let mut map = HashMap::new();
+        let lines = read_lines(filepath);
+        if lines.is_err() {
+            return Err(());
+        }
+        let mut lines = lines.unwrap();
+
+        let head = lines.next();
+
+        if head.is_none() {
+            return Err(());
+        }
+
+        let head = head.unwrap().map_err(|_| ())?;
+        let head = Lexer::try_token_vec(&head).map_err(|_| ())?;
+        let mut head_after = Vec::new();
+        for item in head {
+            if let Token::Symbol(s) = item {
+                head_after.push(s)
+            } else {
+                return Err(());
+            }
+        }
+        let head = head_after;
+
+        // a row that can not be read is an error, not a row less
+        for line in lines {
+            let l = line.map_err(|_| ())?;
+            let v = Lexer::try_token_vec(l.as_str()).map_err(|_| ())?;
+            match v.first() {
+                Some(Token::Symbol(s)) => map.insert(s.clone(), v),
+                _ => return Err(()),
+            };
+        }
+        Ok(DB { head, tables: map })
+    }
+
+    // an empty table with the fields, written to the file at once
+    pub fn create(filepath: &str, head: &[&str]) -> io::Result<DB> {
+        let db = DB {
+            head: head.iter().map(|h| h.to_string()).collect(),
+            tables: HashMap::new(),
+        };
+        db.flush(filepath)?;
+        Ok(db)
+    }
+
+    // the table is written next to the file and then takes its place, a
+    // failed write leaves the file as it was
+    pub fn flush(&self, output: &str) -> io::Result<()> {
+        let mut text = String::new();
+        for i in self.head.iter() {
+            text += &format!("{} ", i);
+        }
+        text += "\n";
+        for v in self.tables.values() {
+            for item in v.iter() {
+                text += &match item {
+                    Token::String(x) => format!("'{}' ", x),
+                    Token::Symbol(s) => format!("{} ", s),
+                    Token::Int(s) => format!("{} ", s),
+                    Token::Float(s) => format!("{} ", s),
+                    t => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("{:?} can not be kept in a table", t),
+                        ))
+                    }
+                };
+            }
+            text += "\n";
+        }
+
+        let tmp = format!("{}.tmp", output);
+        let mut f = File::create(&tmp)?;
+        f.write_all(text.as_bytes())?;
+        f.sync_all()?;
+        fs::rename(&tmp, output)
+    }
+    fn field_to_int(&self,field:&str) -> Option<usize> {
+        let mut i:usize=0;
+        for name in self.head.iter() {
+            if name==field{
+                return Some(i)
+            }
+            i += 1;
+        }
+        None
+    }
+
+    pub fn set(&mut self,key :&str,field:&str,value:Token) -> Result<(),()>{
+        let index  = self.field_to_int(field);
+        if index.is_none(){
+            return Err(())
+        }
+        let index = index.unwrap();
+        if let Some(x) = self.tables.get_mut(key){
+            x.remove(index);
+            x.insert(index, value);
+            return Ok(())
+        }
+
+        Err(())
+    }
+
+    pub fn get(&mut self,key :&str,field:&str) -> Result<Token,()>{
+        let index  = self.field_to_int(field);
+        if index.is_none(){
+            return Err(())
+        }
+        let index = index.unwrap();
+        if let Some(x) = self.tables.get_mut(key){
+            Ok(x.get(index).unwrap().clone())
+        }else{
+            Err(())
+        }
+    }
+    // data is the whole row, the key first
+    pub fn insert(&mut self,key :&str,data:Vec<Token>) {
+        self.tables.insert(key.to_string(), data);
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.tables.contains_key(key)
+    }
+
+    pub fn has_field(&self, field: &str) -> bool {
+        self.field_to_int(field).is_some()
+    }
+
+    // a new last field, value in every row kept so far
+    pub fn add_field(&mut self, field: &str, value: Token) {
+        self.head.push(field.to_string());
+        for row in self.tables.values_mut() {
+            row.push(value.clone());
+        }
+    }
+
+
+    pub fn deal_cmd(&mut self, s:&str) -> Result<(),()> {
+        let tokens = Lexer::to_token_vec(s);
+        self.get_deal(&tokens);
+        self.insert_deal(&tokens);
+        Ok(())
+    }
+    
+    fn get_deal(&mut self,v:&Vec<Token>) {
+        match  v.get(0).cloned() {
+            Some(Token::Symbol(x)) => {
+                if x.as_str() == "get" {
+                    if let Some(Token::Symbol(key)) = v.get(1){
+                        if let Some(Token::Symbol(field)) = v.get(2){
+                            println!("{:?}",self.get(key, field));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn insert_deal(&mut self,v:&Vec<Token>) {
+            if let Some(Token::Symbol(x)) = v.get(0).clone(){
+                if x.as_str() == "insert" {
+                    if let Some(Token::Symbol(key)) = v.get(1){
+                        let mut after = v.clone();
+                        after.remove(0);
+                        self.insert(key, after)
+                    }
+                }
+            }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DB;
+
+    #[test]
+    fn unreadable_files_are_errors() {
+        let path = std::env::temp_dir().join(format!("table-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut db = DB::create(path, &["id", "name"]).unwrap();
+        db.insert("alice", super::Lexer::to_token_vec("alice 'Alice'"));
+        db.flush(path).unwrap();
+        assert!(DB::open(path).unwrap().contains("alice"));
+
+        for text in ["id name\nalice 'Alice'\n\n", "id name\nalice 'Alice\n"] {
+            std::fs::write(path, text).unwrap();
+            assert!(DB::open(path).is_err());
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+}
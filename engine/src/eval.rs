@@ -0,0 +1,181 @@
+use chess_core::{Camp, ChessBoard, Game, PieceKind};
+
+const KINDS: [PieceKind; 6] = [
+    PieceKind::Pawn,
+    PieceKind::Knight,
+    PieceKind::Bishop,
+    PieceKind::Rook,
+    PieceKind::Queen,
+    PieceKind::King,
+];
+
+// the piece-square tables of the "simplified evaluation function", from the
+// side of white and a8 first, so that they read like a diagram of the board
+#[rustfmt::skip]
+const PAWN: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+#[rustfmt::skip]
+const BISHOP: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const ROOK: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const QUEEN: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+// the king hides behind its pawns while the queens are on the board
+#[rustfmt::skip]
+const KING: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+// and walks to the centre in the endgame
+#[rustfmt::skip]
+const KING_ENDGAME: [i32; 64] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+// the material value in centipawns, the king is never traded
+pub fn value(kind: PieceKind) -> i32 {
+    match kind {
+        PieceKind::Pawn => 100,
+        PieceKind::Knight => 320,
+        PieceKind::Bishop => 330,
+        PieceKind::Rook => 500,
+        PieceKind::Queen => 900,
+        PieceKind::King => 0,
+    }
+}
+
+// the score of the position in centipawns, from the side of the player to move
+pub fn evaluate(game: &Game) -> i32 {
+    let board = &game.board;
+    let endgame = is_endgame(board);
+
+    let mut score = 0;
+    for camp in [Camp::White, Camp::Black] {
+        let sign = if camp == game.stage().turn() { 1 } else { -1 };
+        for kind in KINDS {
+            let mut bits = board.bitboard(kind, camp);
+            while bits != 0 {
+                let square = bits.trailing_zeros() as usize;
+                score += sign * (value(kind) + square_bonus(kind, camp, square, endgame));
+                bits &= bits - 1;
+            }
+        }
+    }
+    score
+}
+
+// the bonus of the piece on the square x + 8 * y
+fn square_bonus(kind: PieceKind, camp: Camp, square: usize, endgame: bool) -> i32 {
+    let (x, y) = (square % 8, square / 8);
+    // the tables start on the 8th rank of white, which is the 1st one of black
+    let i = match camp {
+        Camp::White => (7 - y) * 8 + x,
+        Camp::Black => y * 8 + x,
+    };
+    match kind {
+        PieceKind::Pawn => PAWN[i],
+        PieceKind::Knight => KNIGHT[i],
+        PieceKind::Bishop => BISHOP[i],
+        PieceKind::Rook => ROOK[i],
+        PieceKind::Queen => QUEEN[i],
+        PieceKind::King if endgame => KING_ENDGAME[i],
+        PieceKind::King => KING[i],
+    }
+}
+
+// no queens, or a queen with at most one minor piece beside it
+fn is_endgame(board: &ChessBoard) -> bool {
+    [Camp::White, Camp::Black].into_iter().all(|camp| {
+        let count = |kind| board.bitboard(kind, camp).count_ones();
+        count(PieceKind::Queen) == 0
+            || (count(PieceKind::Rook) == 0
+                && count(PieceKind::Knight) + count(PieceKind::Bishop) <= 1)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::evaluate;
+    use chess_core::Game;
+
+    #[test]
+    fn scores_from_the_side_to_move() {
+        assert_eq!(evaluate(&Game::new()), 0);
+
+        // white is a queen up
+        let white = Game::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        let black = Game::from_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1").unwrap();
+        assert!(evaluate(&white) > 800);
+        assert_eq!(evaluate(&white), -evaluate(&black));
+
+        // a knight in the centre is worth more than on the rim
+        let centre = Game::from_fen("4k3/8/8/8/4N3/8/8/4K3 w - - 0 1").unwrap();
+        let rim = Game::from_fen("4k3/8/8/8/7N/8/8/4K3 w - - 0 1").unwrap();
+        assert!(evaluate(&centre) > evaluate(&rim));
+    }
+}
@@ -0,0 +1,15 @@
+// a computer player for chess-core: an iterative deepening alpha-beta search
+// with a quiescence search, evaluating the material and the squares of the pieces
+mod eval;
+mod search;
+pub mod uci;
+
+pub use eval::{evaluate, value};
+pub use search::{Engine, Limits, SearchResult, MATE, MAX_DEPTH};
+
+use chess_core::Game;
+
+// the best move of the game within the limits, with a fresh engine
+pub fn search(game: &Game, limits: &Limits) -> SearchResult {
+    Engine::new().search(game, limits)
+}
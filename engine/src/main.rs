@@ -0,0 +1,8 @@
+use std::io;
+
+// speaks the Universal Chess Interface on stdin and stdout, for chess GUIs
+// and tournament managers, or by hand:
+// printf "position startpos moves e2e4\ngo depth 5\n" | cargo run -p engine --release
+fn main() {
+    engine::uci::run(io::stdin().lock(), io::stdout());
+}
@@ -0,0 +1,527 @@
+use crate::eval::{evaluate, value};
+use chess_core::{Cmd, Game, MoveCmd, Piece, PieceKind, Vec2};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// the score of a checkmate at the root, a mate in n plies scores MATE - n
+pub const MATE: i32 = 100_000;
+const INFINITY: i32 = MATE + 1;
+pub const MAX_DEPTH: u32 = 64;
+const MAX_PLY: usize = 128;
+// entries of the transposition table before it is cleared
+const TABLE_SIZE: usize = 1 << 20;
+
+// when the search ends: after the depth, or once the time is spent,
+// whichever comes first
+#[derive(Debug, Copy, Clone)]
+pub struct Limits {
+    pub depth: u32,
+    pub time: Option<Duration>,
+}
+
+impl Limits {
+    pub fn depth(depth: u32) -> Limits {
+        Limits { depth, time: None }
+    }
+
+    pub fn time(time: Duration) -> Limits {
+        Limits {
+            depth: MAX_DEPTH,
+            time: Some(time),
+        }
+    }
+
+    pub fn with_time(mut self, time: Duration) -> Limits {
+        self.time = Some(time);
+        self
+    }
+}
+
+// the outcome of the last completed iteration
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    // None when the game is over
+    pub best: Option<Cmd>,
+    // the moves both sides are expected to play, best first
+    pub pv: Vec<Cmd>,
+    // in centipawns from the side of the player to move
+    pub score: i32,
+    pub depth: u32,
+    pub nodes: u64,
+    pub time: Duration,
+}
+
+impl SearchResult {
+    // the moves until checkmate, negative when the player to move is the one mated
+    pub fn mate_in(&self) -> Option<i32> {
+        if self.score.abs() < MATE - MAX_PLY as i32 {
+            return None;
+        }
+        let moves = (MATE - self.score.abs() + 1) / 2;
+        Some(if self.score > 0 { moves } else { -moves })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Bound {
+    Exact,
+    // the score is at least the stored one
+    Lower,
+    // the score is at most the stored one
+    Upper,
+}
+
+struct Entry {
+    depth: u32,
+    score: i32,
+    bound: Bound,
+    best: Option<MoveCmd>,
+}
+
+// keeps the transposition table, the killer moves and the history
+// of the quiet moves from one search to the next
+pub struct Engine {
+    stop: Arc<AtomicBool>,
+    table: HashMap<u64, Entry>,
+    killers: Vec<[Option<MoveCmd>; 2]>,
+    history: Vec<[u32; 64]>,
+    // the hashes of the positions before the current one, for the repetitions
+    path: Vec<u64>,
+    nodes: u64,
+    start: Instant,
+    time: Option<Duration>,
+    // the first iteration always completes, so that there is a move to play
+    can_abort: bool,
+    aborted: bool,
+}
+
+fn index(pos: Vec2) -> usize {
+    (pos.x() + 8 * pos.y()) as usize
+}
+
+// mate scores are stored relative to the node, not to the root
+fn to_table(score: i32, ply: usize) -> i32 {
+    if score > MATE - MAX_PLY as i32 {
+        score + ply as i32
+    } else if score < -MATE + MAX_PLY as i32 {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn from_table(score: i32, ply: usize) -> i32 {
+    if score > MATE - MAX_PLY as i32 {
+        score - ply as i32
+    } else if score < -MATE + MAX_PLY as i32 {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+fn moves_of(cmds: Vec<Cmd>) -> Vec<MoveCmd> {
+    cmds.into_iter()
+        .filter_map(|c| match c {
+            Cmd::Move(m) => Some(m),
+            _ => None,
+        })
+        .collect()
+}
+
+// the value of the captured piece, en passant included
+fn captured_value(game: &Game, m: &MoveCmd) -> Option<i32> {
+    if let Some(p) = game.board.get_piece(m.to) {
+        return Some(value(p.kind()));
+    }
+    match game.board.get_piece(m.from) {
+        Some(Piece::Pawn(_)) if m.from.x() != m.to.x() => Some(value(PieceKind::Pawn)),
+        _ => None,
+    }
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine {
+            stop: Arc::new(AtomicBool::new(false)),
+            table: HashMap::new(),
+            killers: vec![[None, None]; MAX_PLY + 1],
+            history: vec![[0; 64]; 64],
+            path: Vec::new(),
+            nodes: 0,
+            start: Instant::now(),
+            time: None,
+            can_abort: false,
+            aborted: false,
+        }
+    }
+
+    // setting the flag from another thread ends the search with the best move
+    // found so far; the search does not clear it, clear it before the next one
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    pub fn search(&mut self, game: &Game, limits: &Limits) -> SearchResult {
+        self.search_with(game, limits, |_| {})
+    }
+
+    // iterative deepening, calls on_iteration after every completed depth
+    pub fn search_with(
+        &mut self,
+        game: &Game,
+        limits: &Limits,
+        mut on_iteration: impl FnMut(&SearchResult),
+    ) -> SearchResult {
+        self.start = Instant::now();
+        self.time = limits.time;
+        self.nodes = 0;
+        self.aborted = false;
+        self.killers = vec![[None, None]; MAX_PLY + 1];
+
+        let mut result = SearchResult {
+            best: None,
+            pv: Vec::new(),
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            time: Duration::ZERO,
+        };
+        if game.result().is_some() {
+            return result;
+        }
+
+        // a copy without the history, which every node would clone otherwise
+        let root = Game::from_fen(&game.to_fen()).unwrap();
+        let hashes = game.position_hashes();
+        self.path = hashes[..hashes.len().saturating_sub(1)].to_vec();
+
+        for depth in 1..=limits.depth.max(1) {
+            self.can_abort = depth > 1;
+            let mut pv = Vec::new();
+            let score = self.negamax(&root, depth, 0, -INFINITY, INFINITY, &mut pv);
+            if self.aborted {
+                break;
+            }
+
+            result = SearchResult {
+                best: pv.first().cloned().map(Cmd::Move),
+                pv: pv.into_iter().map(Cmd::Move).collect(),
+                score,
+                depth,
+                nodes: self.nodes,
+                time: self.start.elapsed(),
+            };
+            on_iteration(&result);
+
+            // a forced mate is not found any faster deeper
+            if score.abs() >= MATE - depth as i32 {
+                break;
+            }
+            // the next iteration would not end in the time left
+            if let Some(time) = self.time {
+                if self.start.elapsed() * 2 > time {
+                    break;
+                }
+            }
+        }
+        result
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if !self.can_abort {
+            return false;
+        }
+        if self.stop.load(Ordering::Relaxed) {
+            self.aborted = true;
+        }
+        if let Some(time) = self.time {
+            if self.nodes.is_multiple_of(1024) && self.start.elapsed() >= time {
+                self.aborted = true;
+            }
+        }
+        self.aborted
+    }
+
+    fn negamax(
+        &mut self,
+        game: &Game,
+        depth: u32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<MoveCmd>,
+    ) -> i32 {
+        self.nodes += 1;
+        pv.clear();
+        if self.should_stop() {
+            return 0;
+        }
+
+        let hash = game.hash();
+        if ply > 0 && (game.stage().halfmove_clock() >= 100 || self.path.contains(&hash)) {
+            return 0;
+        }
+        if depth == 0 || ply >= MAX_PLY {
+            return self.quiesce(game, ply, alpha, beta);
+        }
+
+        let mut table_move = None;
+        if let Some(entry) = self.table.get(&hash) {
+            table_move = entry.best.clone();
+            let score = from_table(entry.score, ply);
+            if ply > 0 && entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => {}
+                }
+            }
+        }
+
+        let mut moves = moves_of(game.valid_cmds());
+        if moves.is_empty() {
+            return if game.in_check() {
+                -MATE + ply as i32
+            } else {
+                0
+            };
+        }
+        self.order(game, &mut moves, table_move.as_ref(), ply);
+
+        let alpha_before = alpha;
+        let mut best = -INFINITY;
+        let mut best_move = None;
+        self.path.push(hash);
+        for m in moves {
+            let mut child = game.clone();
+            child.make_move(&m);
+            let mut child_pv = Vec::new();
+            let score = -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+            if self.aborted {
+                self.path.pop();
+                return 0;
+            }
+
+            if score > best {
+                best = score;
+                best_move = Some(m.clone());
+                if score > alpha {
+                    alpha = score;
+                    pv.clear();
+                    pv.push(m.clone());
+                    pv.extend(child_pv);
+                }
+            }
+            if alpha >= beta {
+                if captured_value(game, &m).is_none() && m.promotion.is_none() {
+                    self.remember_quiet(&m, depth, ply);
+                }
+                break;
+            }
+        }
+        self.path.pop();
+
+        let bound = if best <= alpha_before {
+            Bound::Upper
+        } else if best >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        if self.table.len() >= TABLE_SIZE {
+            self.table.clear();
+        }
+        self.table.insert(
+            hash,
+            Entry {
+                depth,
+                score: to_table(best, ply),
+                bound,
+                best: best_move,
+            },
+        );
+        best
+    }
+
+    // follows the captures and promotions until the position is quiet, so that
+    // the evaluation is not taken in the middle of an exchange
+    fn quiesce(&mut self, game: &Game, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+
+        let in_check = game.in_check();
+        let mut moves = moves_of(game.valid_cmds());
+        if moves.is_empty() {
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+        if ply >= MAX_PLY {
+            return evaluate(game);
+        }
+
+        // a player in check must answer it, otherwise standing still is an option
+        let mut best = -INFINITY;
+        if !in_check {
+            best = evaluate(game);
+            if best >= beta {
+                return best;
+            }
+            alpha = alpha.max(best);
+            moves.retain(|m| m.promotion.is_some() || captured_value(game, m).is_some());
+        }
+        self.order(game, &mut moves, None, ply);
+
+        for m in moves {
+            let mut child = game.clone();
+            child.make_move(&m);
+            let score = -self.quiesce(&child, ply + 1, -beta, -alpha);
+            if self.aborted {
+                return 0;
+            }
+            best = best.max(score);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+
+    // the move of the transposition table, then the captures of the most valuable
+    // pieces by the least valuable ones, the promotions, the killers and the
+    // quiet moves which caused most cutoffs
+    fn order(&self, game: &Game, moves: &mut [MoveCmd], table_move: Option<&MoveCmd>, ply: usize) {
+        let killers = &self.killers[ply.min(MAX_PLY)];
+        moves.sort_by_cached_key(|m| {
+            let score = if Some(m) == table_move {
+                1_000_000
+            } else if let Some(victim) = captured_value(game, m) {
+                let attacker = game.board.get_piece(m.from).map_or(0, |p| value(p.kind()));
+                100_000 + 10 * victim - attacker
+            } else if let Some(kind) = m.promotion {
+                90_000 + value(kind)
+            } else if killers[0].as_ref() == Some(m) {
+                80_000
+            } else if killers[1].as_ref() == Some(m) {
+                79_000
+            } else {
+                self.history[index(m.from)][index(m.to)].min(78_000) as i32
+            };
+            Reverse(score)
+        });
+    }
+
+    fn remember_quiet(&mut self, m: &MoveCmd, depth: u32, ply: usize) {
+        let killers = &mut self.killers[ply.min(MAX_PLY)];
+        if killers[0].as_ref() != Some(m) {
+            killers[1] = killers[0].take();
+            killers[0] = Some(m.clone());
+        }
+        let history = &mut self.history[index(m.from)][index(m.to)];
+        *history = history.saturating_add(depth * depth);
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Engine, Limits, MATE};
+    use chess_core::{Cmd, Game, MoveCmd, Vec2};
+    use std::time::{Duration, Instant};
+
+    fn uci_move(uci: &str) -> Cmd {
+        Cmd::Move(MoveCmd::new(
+            Vec2::from_square(&uci[..2]).unwrap(),
+            Vec2::from_square(&uci[2..]).unwrap(),
+        ))
+    }
+
+    #[test]
+    fn finds_the_mate_in_one() {
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = Engine::new().search(&game, &Limits::depth(3));
+        assert_eq!(result.best, Some(uci_move("a1a8")));
+        assert_eq!(result.score, MATE - 1);
+        assert_eq!(result.mate_in(), Some(1));
+        assert_eq!(result.pv, vec![uci_move("a1a8")]);
+    }
+
+    #[test]
+    fn finds_the_mate_in_two() {
+        // Kb6 Kb8 Qh8#
+        let game = Game::from_fen("k7/8/2K5/8/8/8/8/7Q w - - 0 1").unwrap();
+        let result = Engine::new().search(&game, &Limits::depth(5));
+        assert_eq!(result.mate_in(), Some(2));
+        assert_eq!(result.pv.len(), 3);
+        let mut after = game.clone();
+        for cmd in &result.pv {
+            after.exec_cmd(cmd).unwrap();
+        }
+        assert_eq!(
+            after.result().unwrap().winner(),
+            Some(chess_core::Camp::White)
+        );
+    }
+
+    #[test]
+    fn wins_material_and_keeps_its_own() {
+        let game = Game::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let result = Engine::new().search(&game, &Limits::depth(2));
+        assert_eq!(result.best, Some(uci_move("d2d5")));
+        assert!(result.score > 400);
+
+        // the queen is defended, it is still worth the rook
+        let game = Game::from_fen("4k3/8/4p3/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let result = Engine::new().search(&game, &Limits::depth(3));
+        assert_eq!(result.best, Some(uci_move("d2d5")));
+
+        // the rook is defended, it is not worth the queen
+        let game = Game::from_fen("4k3/8/1p6/2r5/8/8/2Q5/4K3 w - - 0 1").unwrap();
+        let result = Engine::new().search(&game, &Limits::depth(3));
+        assert_ne!(result.best, Some(uci_move("c2c5")));
+    }
+
+    #[test]
+    fn stops_at_the_time_limit() {
+        let start = Instant::now();
+        let limits = Limits::time(Duration::from_millis(200));
+        let result = Engine::new().search(&Game::new(), &limits);
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(result.depth >= 1);
+        assert!(Game::new()
+            .valid_cmds()
+            .contains(result.best.as_ref().unwrap()));
+
+        // a stopped search still plays the move of the first iteration
+        let mut engine = Engine::new();
+        engine
+            .stop_flag()
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+        let result = engine.search(&Game::new(), &Limits::depth(10));
+        assert_eq!(result.depth, 1);
+        assert!(result.best.is_some());
+    }
+
+    #[test]
+    fn no_move_when_the_game_is_over() {
+        let mut game = Game::new();
+        for m in ["f3", "e5", "g4", "Qh4#"] {
+            let cmd = game.parse_san(m).unwrap();
+            game.exec_cmd(&cmd).unwrap();
+        }
+        let result = Engine::new().search(&game, &Limits::depth(3));
+        assert!(result.best.is_none());
+        assert!(result.pv.is_empty());
+    }
+}
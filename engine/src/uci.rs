@@ -0,0 +1,261 @@
+use crate::{Engine, Limits, SearchResult};
+use chess_core::{Camp, Game};
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// the Universal Chess Interface: reads the commands of a GUI from input and
+// answers on output, the search runs in its own thread so that "stop" is heard
+pub fn run<R: BufRead, W: Write + Send + 'static>(input: R, output: W) {
+    let mut uci = Uci::new(output);
+    for line in input.lines() {
+        let line = match line {
+            Ok(l) => l,
+            Err(_) => break,
+        };
+        if !uci.command(&line) {
+            uci.stop();
+            return;
+        }
+    }
+    // piped input ends before the search does, let it finish unless it never would
+    if uci.infinite {
+        uci.stop();
+    }
+    uci.wait();
+}
+
+struct Uci<W> {
+    game: Game,
+    engine: Arc<Mutex<Engine>>,
+    stop: Arc<AtomicBool>,
+    search: Option<JoinHandle<()>>,
+    infinite: bool,
+    output: Arc<Mutex<W>>,
+}
+
+fn send<W: Write>(output: &Mutex<W>, line: &str) {
+    let mut output = output.lock().unwrap();
+    let _ = writeln!(output, "{}", line);
+    let _ = output.flush();
+}
+
+// "info depth 5 score cp 20 nodes 12345 time 120 pv e2e4 e7e5"
+fn info(game: &Game, result: &SearchResult) -> String {
+    let score = match result.mate_in() {
+        Some(n) => format!("mate {}", n),
+        None => format!("cp {}", result.score),
+    };
+    let pv: Vec<String> = result
+        .pv
+        .iter()
+        .filter_map(|c| game.to_uci(c).ok())
+        .collect();
+    format!(
+        "info depth {} score {} nodes {} time {} pv {}",
+        result.depth,
+        score,
+        result.nodes,
+        result.time.as_millis(),
+        pv.join(" ")
+    )
+}
+
+impl<W: Write + Send + 'static> Uci<W> {
+    fn new(output: W) -> Self {
+        let engine = Engine::new();
+        Uci {
+            game: Game::new(),
+            stop: engine.stop_flag(),
+            engine: Arc::new(Mutex::new(engine)),
+            search: None,
+            infinite: false,
+            output: Arc::new(Mutex::new(output)),
+        }
+    }
+
+    // false once the GUI quits
+    fn command(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.first() {
+            Some(&"uci") => {
+                send(&self.output, "id name chess");
+                send(&self.output, "id author linzhi555");
+                send(&self.output, "uciok");
+            }
+            Some(&"isready") => send(&self.output, "readyok"),
+            Some(&"ucinewgame") => {
+                self.stop();
+                self.game = Game::new();
+                *self.engine.lock().unwrap() = Engine::new();
+                self.stop = self.engine.lock().unwrap().stop_flag();
+            }
+            Some(&"position") => {
+                self.stop();
+                if let Err(e) = self.position(&words[1..]) {
+                    send(&self.output, &format!("info string {}", e));
+                }
+            }
+            Some(&"go") => self.go(&words[1..]),
+            Some(&"stop") => self.stop(),
+            Some(&"quit") => return false,
+            // debug, setoption, register and ponderhit change nothing here
+            _ => {}
+        }
+        true
+    }
+
+    // "startpos" or "fen <fen>", then optionally "moves e2e4 e7e5 ..."
+    fn position(&mut self, words: &[&str]) -> Result<(), String> {
+        let moves_at = words.iter().position(|w| *w == "moves");
+        let (setup, moves) = match moves_at {
+            Some(i) => (&words[..i], &words[i + 1..]),
+            None => (words, &[][..]),
+        };
+
+        let mut game = match setup.first() {
+            Some(&"startpos") => Game::new(),
+            Some(&"fen") => Game::from_fen(&setup[1..].join(" ")).map_err(|e| e.to_string())?,
+            _ => return Err(format!("unknown position {}", setup.join(" "))),
+        };
+        for m in moves {
+            let cmd = game.parse_uci(m).map_err(|e| format!("{}: {}", m, e))?;
+            game.exec_cmd(&cmd).map_err(|e| format!("{}: {}", m, e))?;
+        }
+        self.game = game;
+        Ok(())
+    }
+
+    fn go(&mut self, words: &[&str]) {
+        self.stop();
+        let limits = self.limits(words);
+        self.infinite = limits.depth == crate::MAX_DEPTH && limits.time.is_none();
+
+        self.stop.store(false, Ordering::Relaxed);
+        let game = self.game.clone();
+        let engine = self.engine.clone();
+        let output = self.output.clone();
+        self.search = Some(thread::spawn(move || {
+            let mut engine = engine.lock().unwrap();
+            let result = engine.search_with(&game, &limits, |r| send(&output, &info(&game, r)));
+            let best = match &result.best {
+                Some(c) => game.to_uci(c).unwrap(),
+                None => "0000".to_string(),
+            };
+            send(&output, &format!("bestmove {}", best));
+        }));
+    }
+
+    // "depth 6", "movetime 1000", "wtime 60000 btime 60000 winc 1000 binc 1000
+    // movestogo 20" or "infinite"
+    fn limits(&self, words: &[&str]) -> Limits {
+        let value = |name: &str| {
+            let i = words.iter().position(|w| *w == name)?;
+            words.get(i + 1)?.parse::<u64>().ok()
+        };
+
+        let mut limits = Limits::depth(crate::MAX_DEPTH);
+        if let Some(depth) = value("depth") {
+            limits.depth = depth as u32;
+        }
+        if let Some(ms) = value("movetime") {
+            limits.time = Some(Duration::from_millis(ms));
+        }
+
+        let (time, inc) = match self.game.stage().turn() {
+            Camp::White => (value("wtime"), value("winc")),
+            Camp::Black => (value("btime"), value("binc")),
+        };
+        if let (Some(time), None) = (time, limits.time) {
+            // a share of the clock, keeping a margin for the moves to come
+            let moves_to_go = value("movestogo").unwrap_or(30).max(1);
+            let ms = time / moves_to_go + inc.unwrap_or(0) * 3 / 4;
+            limits.time = Some(Duration::from_millis(ms.min(time / 2)));
+        }
+        limits
+    }
+
+    // ends the search, which still answers with its best move
+    fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.wait();
+    }
+
+    fn wait(&mut self) {
+        if let Some(search) = self.search.take() {
+            let _ = search.join();
+        }
+        self.infinite = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+    use std::io::{Cursor, Write};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn answer(input: &str) -> Vec<String> {
+        let output = Output(Arc::new(Mutex::new(Vec::new())));
+        run(Cursor::new(input.to_string()), output.clone());
+        let bytes = output.0.lock().unwrap().clone();
+        String::from_utf8(bytes)
+            .unwrap()
+            .lines()
+            .map(|l| l.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn handshake() {
+        let lines = answer("uci\nisready\nquit\n");
+        assert_eq!(lines.first().unwrap(), "id name chess");
+        assert!(lines.contains(&"uciok".to_string()));
+        assert_eq!(lines.last().unwrap(), "readyok");
+    }
+
+    #[test]
+    fn plays_from_the_position_and_its_moves() {
+        // after 1.f3 e5 2.g4, black mates with Qh4
+        let lines = answer("position startpos moves f2f3 e7e5 g2g4\ngo depth 3\n");
+        assert!(lines[0].starts_with("info depth 1 score mate 1"));
+        assert_eq!(lines.last().unwrap(), "bestmove d8h4");
+
+        let lines = answer("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo movetime 500\n");
+        assert_eq!(lines.last().unwrap(), "bestmove a1a8");
+
+        // a pawn promotes with the letter of the piece
+        let lines = answer("position fen 8/1P5k/8/8/8/8/8/K7 w - - 0 1\ngo depth 1\n");
+        assert_eq!(lines.last().unwrap(), "bestmove b7b8q");
+
+        let lines = answer("position startpos moves e2e5\ngo depth 1\n");
+        assert_eq!(lines[0], "info string e2e5: illegal move");
+    }
+
+    #[test]
+    fn stop_answers_with_the_best_move() {
+        let lines = answer("position startpos\ngo infinite\nstop\nquit\n");
+        assert!(lines.last().unwrap().starts_with("bestmove "));
+        let lines = answer("position startpos\ngo wtime 1000 btime 1000\n");
+        assert!(lines.last().unwrap().starts_with("bestmove "));
+
+        // checkmated, there is no move to play
+        let lines = answer("position startpos moves f2f3 e7e5 g2g4 d8h4\ngo depth 2\n");
+        assert_eq!(lines, vec!["bestmove 0000"]);
+    }
+}
@@ -37,6 +37,13 @@ impl Lexer {
         lexer.result
     }
 
+    // the tokens of s, or why it has none, for input that must not panic
+    pub fn try_token_vec(s: &str) -> Result<Vec<Token>, String> {
+        let mut lexer = Lexer::new();
+        lexer.try_tokenize(s)?;
+        Ok(lexer.result)
+    }
+
     pub fn add_keyword(&mut self, k: &str) {
         self.keywords.push(k.to_string());
     }
@@ -52,6 +59,12 @@ impl Lexer {
     }
 
     pub fn tokenize(&mut self, input: &str) {
+        if let Err(e) = self.try_tokenize(input) {
+            panic!("{}", e)
+        }
+    }
+
+    pub fn try_tokenize(&mut self, input: &str) -> Result<(), String> {
         let mut stream = input.chars().peekable();
         loop {
             let c: char;
@@ -127,7 +140,7 @@ impl Lexer {
                 } else if let Ok(f) = buffer.parse::<f32>() {
                     self.result.push(Token::Float(f));
                 } else {
-                    panic!("numeric parse error: {}", buffer)
+                    return Err(format!("numeric parse error: {}", buffer));
                 }
             }
 
@@ -155,7 +168,7 @@ impl Lexer {
                 let mut buffer = String::new();
                 loop {
                     if stream.peek().is_none() {
-                        panic!("string tokenize error")
+                        return Err("string tokenize error".to_string());
                     } else if stream.peek().unwrap().clone() == '\'' {
                         stream.next();
                         break;
@@ -167,5 +180,6 @@ impl Lexer {
                 self.result.push(Token::String(buffer))
             }
         }
+        Ok(())
     }
 }
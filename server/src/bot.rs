@@ -0,0 +1,91 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::{rt, web};
+
+use engine::Limits;
+
+use crate::rooms::{now, Room};
+
+// deeper searches take minutes a move
+pub const MAX_DEPTH: u32 = 6;
+// a bot never thinks longer than this, whatever its depth
+const MAX_TIME: Duration = Duration::from_secs(10);
+
+// plays the move of the bot when it is its turn, in the background so that
+// the cmd of the player is answered at once
+pub fn reply(room: Arc<Mutex<Room>>) {
+    rt::spawn(async move {
+        let (game, bot, left) = {
+            let room = room.lock().unwrap();
+            match room.bot {
+                Some(bot)
+                    if room.game.result().is_none() && room.game.stage().turn() == bot.camp =>
+                {
+                    let left = room.clock.as_ref().map(|c| c.remaining(bot.camp, now()));
+                    (room.game.clone(), bot, left)
+                }
+                _ => return,
+            }
+        };
+
+        let plies = game.history().len();
+        // a share of its clock, as if 30 moves were left to play
+        let time = left.map_or(MAX_TIME, |ms| MAX_TIME.min(Duration::from_millis(ms / 30)));
+        let limits = Limits::depth(bot.depth.clamp(1, MAX_DEPTH)).with_time(time);
+        let result = web::block(move || engine::search(&game, &limits)).await;
+
+        if let Ok(Some(cmd)) = result.map(|r| r.best) {
+            let mut room = room.lock().unwrap();
+            // the bot is the only one to move on its turn
+            if room.game.history().len() == plies {
+                match room.exec(&cmd) {
+                    Ok(Err(e)) => println!("bot move {:?} refused: {}", cmd, e),
+                    Err(e) => println!("bot move {:?} not played: {}", cmd, e),
+                    Ok(Ok(())) => {}
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chess_core::{Camp, Game};
+    use server::BotConfig;
+
+    use crate::rooms::Rooms;
+
+    #[actix_web::test]
+    async fn the_bot_answers_a_move() {
+        let rooms = Rooms::default();
+        let bot = BotConfig {
+            camp: Camp::Black,
+            depth: 1,
+        };
+        let room = rooms.create(Some(bot), None).unwrap();
+        let e4 = {
+            let mut room = room.lock().unwrap();
+            let e4 = room.game.parse_san("e4").unwrap();
+            room.exec(&e4).unwrap().unwrap();
+            e4
+        };
+        super::reply(room.clone());
+
+        for _ in 0..100 {
+            if room.lock().unwrap().game.history().len() == 2 {
+                break;
+            }
+            actix_web::rt::time::sleep(Duration::from_millis(50)).await;
+        }
+        let room = room.lock().unwrap();
+        assert_eq!(room.game.history().len(), 2);
+        assert_eq!(room.game.stage().turn(), Camp::White);
+
+        let mut game = Game::new();
+        game.exec_cmd(&e4).unwrap();
+        assert!(game.valid_cmds().contains(&room.game.history()[1].cmd));
+    }
+}
@@ -1,3 +1,4 @@
+use chess_core::{Camp, ChessError, Cmd, Game, GameResult, TimeControl};
 use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, Hash, PartialEq)]
 pub struct LoginRequest {
@@ -11,3 +12,179 @@ pub struct LoginResponse {
     pub err: String,
     pub token: String,
 }
+
+// the answer to a game cmd, err tells why the cmd was refused
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct CmdResponse {
+    pub ok: bool,
+    pub err: Option<ChessError>,
+}
+
+// a camp played by the server, the bot looks depth plies ahead
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+pub struct BotConfig {
+    pub camp: Camp,
+    pub depth: u32,
+}
+
+// creates a game, against the bot if there is one; the user creating it
+// plays camp, or the camp the bot does not play, white by default; a game
+// without a clock is never lost on time
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct NewGameRequest {
+    #[serde(default)]
+    pub bot: Option<BotConfig>,
+    #[serde(default)]
+    pub camp: Option<Camp>,
+    #[serde(default)]
+    pub clock: Option<TimeControl>,
+}
+
+// the milliseconds left to each camp when the server sent it, and the camp
+// whose time is running
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct ClockState {
+    pub control: TimeControl,
+    pub white: u64,
+    pub black: u64,
+    pub running: Option<Camp>,
+}
+
+// takes the seat of camp, or the first free one; the user watches
+// the game when it is taken
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct JoinRequest {
+    #[serde(default)]
+    pub camp: Option<Camp>,
+}
+
+// the camp of the user, None for a spectator
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct JoinResponse {
+    pub camp: Option<Camp>,
+}
+
+// a game as listed by GET /games
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct GameSummary {
+    pub id: u64,
+    pub bot: Option<BotConfig>,
+    pub white: Option<String>,
+    pub black: Option<String>,
+    pub turn: Camp,
+    pub result: Option<GameResult>,
+    pub clock: Option<TimeControl>,
+    // the moves played so far, by both camps
+    pub moves: usize,
+}
+
+// a game with its players, as sent by GET /games/{id}/state
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GameState {
+    pub id: u64,
+    pub bot: Option<BotConfig>,
+    pub white: Option<String>,
+    pub black: Option<String>,
+    pub spectators: Vec<String>,
+    pub game: Game,
+    pub clock: Option<ClockState>,
+}
+
+// what GET /games/{id}/ws pushes, as json text messages; the client sends
+// "sync" to get the whole state again
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum GameEvent {
+    // the whole game, sent first and whenever the client is behind
+    State(Box<GameState>),
+    // a cmd accepted, ply is the length of the history before it
+    Cmd {
+        ply: usize,
+        cmd: Cmd,
+        clock: Option<ClockState>,
+    },
+    // the seats and the spectators after a join
+    Players {
+        white: Option<String>,
+        black: Option<String>,
+        spectators: Vec<String>,
+    },
+}
+
+// the terminal colours a user can pick for the pieces of a camp
+pub const COLOURS: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+// the colours of the pieces of each camp on the board of the user
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Colours {
+    pub white: String,
+    pub black: String,
+}
+
+impl Default for Colours {
+    fn default() -> Self {
+        Colours {
+            white: "green".to_string(),
+            black: "red".to_string(),
+        }
+    }
+}
+
+// a new account, display_name is the id when missing;
+// POST /register answers with a LoginResponse or ValidationErrors
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct RegisterRequest {
+    pub id: String,
+    pub password: String,
+    #[serde(default)]
+    pub display_name: Option<String>,
+}
+
+// a user as sent by GET /users/{id}
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Profile {
+    pub id: String,
+    pub display_name: String,
+    pub colours: Colours,
+}
+
+// the new password of the user, who proves it knows the old one
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct PasswordChange {
+    pub old: String,
+    pub new: String,
+}
+
+// PATCH /users/me changes the fields given and answers with the Profile
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq)]
+pub struct ProfileUpdate {
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub colours: Option<Colours>,
+    #[serde(default)]
+    pub password: Option<PasswordChange>,
+}
+
+// why a field of a request was refused
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: &str) -> Self {
+        FieldError {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+// the body of a 400 answer to /register or PATCH /users/me
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct ValidationErrors {
+    pub errors: Vec<FieldError>,
+}
@@ -1,59 +1,272 @@
-use std::sync::Mutex;
+mod bot;
+mod push;
+mod rooms;
+mod sessions;
+mod store;
+mod users;
 
-use actix_web::{get, post, web, App, HttpServer, Responder};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use chess_core::{Cmd, Game};
+use actix_web::error::{ErrorBadRequest, ErrorForbidden, ErrorInternalServerError, ErrorNotFound};
+use actix_web::{
+    get, patch, post, rt, web, App, Error, HttpRequest, HttpResponse, HttpServer, Responder,
+};
 
-use server::{LoginRequest, LoginResponse};
+use chess_core::Cmd;
+
+use rooms::{Room, Rooms};
+use server::{
+    CmdResponse, FieldError, JoinRequest, JoinResponse, LoginRequest, LoginResponse,
+    NewGameRequest, ProfileUpdate, RegisterRequest, ValidationErrors,
+};
+use sessions::Sessions;
+use store::Store;
+use users::Users;
+
+// where the users and their password hashes are kept
+const USERS_DB: &str = "users.db";
+// where the log of each game is kept
+const GAMES_DIR: &str = "games";
+// how late a flag may fall
+const FLAG_CHECK: Duration = Duration::from_millis(100);
 
 #[get("/hello/{name}")]
 async fn greet(name: web::Path<String>) -> impl Responder {
     format!("Hello {name}!")
 }
 
-#[post("/game/cmd")]
-async fn game_cmd(cmd: web::Json<Cmd>, game: web::Data<Mutex<Game>>) -> impl Responder {
-    let mut game = game.lock().unwrap();
-    println!("{:?}",cmd);
-    let cmd_res = game.exec_cmd(&cmd);
-    println!("{:?}", *game);
+// the game of the path, or 404
+fn room_of(id: u64, rooms: &Rooms) -> Result<Arc<Mutex<Room>>, Error> {
+    rooms
+        .get(id)
+        .ok_or_else(|| ErrorNotFound(format!("no game {}", id)))
+}
+
+#[post("/games")]
+async fn games_new(
+    http: HttpRequest,
+    req: web::Json<NewGameRequest>,
+    rooms: web::Data<Rooms>,
+    sessions: web::Data<Sessions>,
+) -> Result<impl Responder, Error> {
+    let user = sessions.user_of(&http)?;
+    if let (Some(bot), Some(camp)) = (req.bot, req.camp) {
+        if bot.camp == camp {
+            return Err(ErrorBadRequest(format!("the bot plays {:?}", camp)));
+        }
+    }
+    let room = rooms
+        .create(req.bot, req.clock)
+        .map_err(ErrorInternalServerError)?;
+    let summary = {
+        let mut room = room.lock().unwrap();
+        let camp = req.camp.or(req.bot.map(|b| b.camp.opposite()));
+        room.join(&user, camp).map_err(ErrorInternalServerError)?;
+        room.summary()
+    };
+    // the bot opens when it plays white
+    bot::reply(room);
+    Ok(web::Json(summary))
+}
+
+#[get("/games")]
+async fn games_list(rooms: web::Data<Rooms>) -> impl Responder {
+    web::Json(rooms.list())
+}
+
+#[post("/games/{id}/join")]
+async fn game_join(
+    http: HttpRequest,
+    id: web::Path<u64>,
+    req: web::Json<JoinRequest>,
+    rooms: web::Data<Rooms>,
+    sessions: web::Data<Sessions>,
+) -> Result<impl Responder, Error> {
+    let user = sessions.user_of(&http)?;
+    let room = room_of(*id, &rooms)?;
+    let camp = room
+        .lock()
+        .unwrap()
+        .join(&user, req.camp)
+        .map_err(ErrorInternalServerError)?;
+    Ok(web::Json(JoinResponse { camp }))
+}
 
-    let response: String;
-    if cmd_res.is_err() {
-        response = cmd_res.err().unwrap().to_string();
-    } else {
-        response = "ok".to_string();
+#[post("/games/{id}/cmd")]
+async fn game_cmd(
+    http: HttpRequest,
+    id: web::Path<u64>,
+    cmd: web::Json<Cmd>,
+    rooms: web::Data<Rooms>,
+    sessions: web::Data<Sessions>,
+) -> Result<impl Responder, Error> {
+    let user = sessions.user_of(&http)?;
+    let room = room_of(*id, &rooms)?;
+    let cmd_res = {
+        let mut room = room.lock().unwrap();
+        room.check_player(&user, &cmd).map_err(ErrorForbidden)?;
+        println!("game {}: {} {:?}", id, user, cmd);
+        room.exec(&cmd).map_err(ErrorInternalServerError)?
+    };
+    if cmd_res.is_ok() {
+        bot::reply(room);
     }
 
-    web::Json(response)
+    Ok(web::Json(CmdResponse {
+        ok: cmd_res.is_ok(),
+        err: cmd_res.err(),
+    }))
+}
+
+#[get("/games/{id}/state")]
+async fn game_state(id: web::Path<u64>, rooms: web::Data<Rooms>) -> Result<impl Responder, Error> {
+    let room = room_of(*id, &rooms)?;
+    let state = room.lock().unwrap().state();
+    Ok(web::Json(state))
+}
+
+// the state of the game, then its changes as they happen, over a websocket
+#[get("/games/{id}/ws")]
+async fn game_ws(
+    http: HttpRequest,
+    body: web::Payload,
+    id: web::Path<u64>,
+    rooms: web::Data<Rooms>,
+) -> Result<HttpResponse, Error> {
+    let room = room_of(*id, &rooms)?;
+    let (response, session, stream) = actix_ws::handle(&http, body)?;
+    rt::spawn(push::serve(room, session, stream));
+    Ok(response)
 }
 
-#[post("/game/state")]
-async fn game_state(game: web::Data<Mutex<Game>>) -> impl Responder {
-    let game = game.lock().unwrap();
-    web::Json(game.clone())
+#[get("/games/{id}/pgn")]
+async fn game_pgn(id: web::Path<u64>, rooms: web::Data<Rooms>) -> Result<impl Responder, Error> {
+    let room = room_of(*id, &rooms)?;
+    let pgn = room
+        .lock()
+        .unwrap()
+        .game
+        .to_pgn(&[("Site", "chess server")]);
+    Ok(pgn)
+}
+
+// a token for the session of the user, or why there is none
+fn login_response(user: Result<String, String>, sessions: &Sessions) -> LoginResponse {
+    match user {
+        Ok(user) => LoginResponse {
+            ok: true,
+            err: String::new(),
+            token: sessions.login(&user),
+        },
+        Err(err) => LoginResponse {
+            ok: false,
+            err,
+            token: String::new(),
+        },
+    }
 }
 
 #[post("/login")]
-async fn login(log_req: web::Json<LoginRequest>) -> impl Responder {
-    println!("{:?}", log_req);
-    web::Json(LoginResponse {
-        ok: true,
-        err: "None".to_string(),
-        token: "ssss".to_string(),
+async fn login(
+    log_req: web::Json<LoginRequest>,
+    users: web::Data<Users>,
+    sessions: web::Data<Sessions>,
+) -> Result<impl Responder, Error> {
+    let LoginRequest { id, password } = log_req.into_inner();
+    // hashing takes a while, off the threads that serve the requests
+    let user = web::block(move || {
+        if users.check(&id, &password) {
+            Ok(id)
+        } else {
+            Err("wrong id or password".to_string())
+        }
     })
+    .await?;
+    Ok(web::Json(login_response(user, &sessions)))
+}
+
+// the answer to a request whose fields were refused
+fn invalid(errors: Vec<FieldError>) -> HttpResponse {
+    HttpResponse::BadRequest().json(ValidationErrors { errors })
+}
+
+#[post("/register")]
+async fn register(
+    reg_req: web::Json<RegisterRequest>,
+    users: web::Data<Users>,
+    sessions: web::Data<Sessions>,
+) -> Result<HttpResponse, Error> {
+    let reg_req = reg_req.into_inner();
+    let id = reg_req.id.clone();
+    let registered = web::block(move || users.register(&reg_req)).await?;
+    match registered.map_err(ErrorInternalServerError)? {
+        Ok(()) => Ok(HttpResponse::Ok().json(login_response(Ok(id), &sessions))),
+        Err(errors) => Ok(invalid(errors)),
+    }
+}
+
+#[get("/users/{id}")]
+async fn user_profile(
+    id: web::Path<String>,
+    users: web::Data<Users>,
+) -> Result<impl Responder, Error> {
+    users
+        .profile(&id)
+        .map(web::Json)
+        .ok_or_else(|| ErrorNotFound(format!("no user {}", id)))
+}
+
+#[patch("/users/me")]
+async fn user_update(
+    http: HttpRequest,
+    update: web::Json<ProfileUpdate>,
+    users: web::Data<Users>,
+    sessions: web::Data<Sessions>,
+) -> Result<HttpResponse, Error> {
+    let user = sessions.user_of(&http)?;
+    let updated = web::block(move || users.update(&user, &update)).await?;
+    match updated.map_err(ErrorInternalServerError)? {
+        Ok(profile) => Ok(HttpResponse::Ok().json(profile)),
+        Err(errors) => Ok(invalid(errors)),
+    }
 }
 
 #[actix_web::main] // or #[tokio::main]
 async fn main() -> std::io::Result<()> {
-    let game: web::Data<Mutex<Game>> = web::Data::new(Mutex::new(Game::new()));
+    let rooms = web::Data::new(Rooms::open(Store::open(GAMES_DIR)?)?);
+    // a bot whose turn it was when the server stopped
+    for game in rooms.list() {
+        bot::reply(rooms.get(game.id).unwrap());
+    }
+    // flags fall even when nobody moves
+    let clocks = rooms.clone();
+    rt::spawn(async move {
+        let mut tick = tokio::time::interval(FLAG_CHECK);
+        loop {
+            tick.tick().await;
+            clocks.check_flags();
+        }
+    });
+    let sessions = web::Data::new(Sessions::default());
+    let users = web::Data::new(Users::open(USERS_DB)?);
     HttpServer::new(move || {
         App::new()
             .service(greet)
+            .service(games_new)
+            .service(games_list)
+            .service(game_join)
             .service(game_cmd)
             .service(game_state)
+            .service(game_ws)
+            .service(game_pgn)
             .service(login)
-            .app_data(game.clone())
+            .service(register)
+            .service(user_profile)
+            .service(user_update)
+            .app_data(rooms.clone())
+            .app_data(users.clone())
+            .app_data(sessions.clone())
     })
     .bind(("127.0.0.1", 8080))?
     .run()
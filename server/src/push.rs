@@ -0,0 +1,64 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_ws::{Message, MessageStream, Session};
+use tokio::sync::broadcast::error::RecvError;
+
+use server::GameEvent;
+
+use crate::rooms::Room;
+
+// a ping this often lets both ends notice a dead connection
+const HEARTBEAT: Duration = Duration::from_secs(5);
+
+fn state(room: &Mutex<Room>) -> GameEvent {
+    GameEvent::State(Box::new(room.lock().unwrap().state()))
+}
+
+// sends the whole game, then every change of it as it happens, until the
+// client goes away
+pub async fn serve(room: Arc<Mutex<Room>>, mut session: Session, mut stream: MessageStream) {
+    // subscribed with the lock held, so that no change falls between the two
+    let (first, mut events) = {
+        let locked = room.lock().unwrap();
+        (
+            GameEvent::State(Box::new(locked.state())),
+            locked.subscribe(),
+        )
+    };
+    let mut next = Some(first);
+    let mut heartbeat = tokio::time::interval(HEARTBEAT);
+
+    loop {
+        if let Some(event) = next.take() {
+            let text = serde_json::to_string(&event).unwrap();
+            if session.text(text).await.is_err() {
+                return;
+            }
+        }
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => next = Some(event),
+                // too far behind, the whole state catches up
+                Err(RecvError::Lagged(_)) => next = Some(state(&room)),
+                Err(RecvError::Closed) => break,
+            },
+            msg = stream.recv() => match msg {
+                Some(Ok(Message::Text(text))) if text.trim() == "sync" => next = Some(state(&room)),
+                Some(Ok(Message::Ping(bytes))) => {
+                    if session.pong(&bytes).await.is_err() {
+                        return;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            _ = heartbeat.tick() => {
+                if session.ping(b"").await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+    let _ = session.close(None).await;
+}
@@ -0,0 +1,623 @@
+use std::collections::HashMap;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chess_core::{Camp, ChessError, Clock, Cmd, Game, TimeControl};
+use tokio::sync::broadcast;
+
+use server::{BotConfig, ClockState, GameEvent, GameState, GameSummary};
+
+use crate::store::{Entry, GameLog, Store};
+
+// the events a slow subscriber may fall behind before it gets the whole state
+const EVENTS: usize = 64;
+
+// the time of the clocks, in milliseconds; it goes on while the server is
+// down, so a clock running then has run all along
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+// a game, the users playing it and the ones watching it; the camp of the
+// bot, if any, has no user
+pub struct Room {
+    pub id: u64,
+    pub game: Game,
+    pub bot: Option<BotConfig>,
+    pub white: Option<String>,
+    pub black: Option<String>,
+    pub spectators: Vec<String>,
+    pub clock: Option<Clock>,
+    events: broadcast::Sender<GameEvent>,
+    // where the seats and the cmds go, none for a game in memory only
+    log: Option<GameLog>,
+    // a write to the log failed, it may end in a torn line
+    log_broken: bool,
+}
+
+impl Room {
+    fn new(
+        id: u64,
+        bot: Option<BotConfig>,
+        clock: Option<TimeControl>,
+        log: Option<GameLog>,
+    ) -> Room {
+        Room {
+            id,
+            game: Game::new(),
+            bot,
+            white: None,
+            black: None,
+            spectators: Vec::new(),
+            clock: clock.map(Clock::new),
+            events: broadcast::channel(EVENTS).0,
+            log,
+            log_broken: false,
+        }
+    }
+
+    // logs the entry before the room changes; once a write has failed nothing
+    // more is written, a replay would stop at the hole, and every change is
+    // refused
+    fn record(&mut self, entry: Entry) -> io::Result<()> {
+        if self.log_broken {
+            return Err(io::Error::other(format!(
+                "game {} is no longer logged",
+                self.id
+            )));
+        }
+        if let Some(log) = &mut self.log {
+            if let Err(e) = log.append(&entry) {
+                println!("game {}: {:?} not logged: {}", self.id, entry, e);
+                self.log = None;
+                self.log_broken = true;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    // the room as it was when the last entry was logged
+    fn replay(id: u64, entries: Vec<Entry>, log: GameLog) -> Room {
+        let mut room = Room::new(id, None, None, Some(log));
+        for entry in entries {
+            match entry {
+                Entry::Created { bot, clock } => {
+                    room.bot = bot;
+                    room.clock = clock.map(Clock::new);
+                }
+                Entry::Seated { user, camp } => *room.seat_mut(camp) = Some(user),
+                Entry::Played { cmd, at } => {
+                    let mover = room.game.stage().turn();
+                    match room.game.exec_cmd(&cmd) {
+                        Ok(()) => room.press_clock(&cmd, mover, at),
+                        Err(e) => println!("game {}: {:?} refused on replay: {}", id, cmd, e),
+                    }
+                }
+                Entry::Flagged { camp, at } => room.flag(camp, at),
+            }
+        }
+        room
+    }
+
+    pub fn summary(&self) -> GameSummary {
+        GameSummary {
+            id: self.id,
+            bot: self.bot,
+            white: self.white.clone(),
+            black: self.black.clone(),
+            turn: self.game.stage().turn(),
+            result: self.game.result(),
+            clock: self.clock.as_ref().map(|c| c.control()),
+            moves: self.game.history().len(),
+        }
+    }
+
+    pub fn state(&self) -> GameState {
+        GameState {
+            id: self.id,
+            bot: self.bot,
+            white: self.white.clone(),
+            black: self.black.clone(),
+            spectators: self.spectators.clone(),
+            game: self.game.clone(),
+            clock: self.clock_state(now()),
+        }
+    }
+
+    fn clock_state(&self, now: u64) -> Option<ClockState> {
+        self.clock.as_ref().map(|c| ClockState {
+            control: c.control(),
+            white: c.remaining(Camp::White, now),
+            black: c.remaining(Camp::Black, now),
+            running: c.running(),
+        })
+    }
+
+    // the changes of the room from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<GameEvent> {
+        self.events.subscribe()
+    }
+
+    // nobody may be listening, which is fine
+    fn publish(&self, event: GameEvent) {
+        let _ = self.events.send(event);
+    }
+
+    // executes the cmd and tells the subscribers about it; the io error is
+    // a legal cmd that could not be logged, and was not played
+    pub fn exec(&mut self, cmd: &Cmd) -> io::Result<Result<(), ChessError>> {
+        self.exec_at(cmd, now())
+    }
+
+    pub fn exec_at(&mut self, cmd: &Cmd, now: u64) -> io::Result<Result<(), ChessError>> {
+        // too late, whatever the cmd
+        if self.check_flag(now) {
+            return Ok(Err(ChessError::GameOver));
+        }
+        let ply = self.game.history().len();
+        let mover = self.game.stage().turn();
+        let mut game = self.game.clone();
+        if let Err(e) = game.exec_cmd(cmd) {
+            return Ok(Err(e));
+        }
+        self.record(Entry::Played {
+            cmd: cmd.clone(),
+            at: now,
+        })?;
+        self.game = game;
+        if let Some(result) = self.game.result() {
+            println!("game {}: finished {:?}", self.id, result);
+        }
+        self.press_clock(cmd, mover, now);
+        self.publish(GameEvent::Cmd {
+            ply,
+            cmd: cmd.clone(),
+            clock: self.clock_state(now),
+        });
+        Ok(Ok(()))
+    }
+
+    // a move passes the turn to the clock of the other camp, the end of the
+    // game stops both
+    fn press_clock(&mut self, cmd: &Cmd, mover: Camp, now: u64) {
+        if let Some(clock) = &mut self.clock {
+            if let Cmd::Move(_) = cmd {
+                clock.press(mover, now);
+            }
+            if self.game.result().is_some() {
+                clock.stop(now);
+            }
+        }
+    }
+
+    fn flag(&mut self, camp: Camp, now: u64) {
+        if let Err(e) = self.game.flag_fall(camp) {
+            println!("game {}: flag of {:?} ignored: {}", self.id, camp, e);
+        }
+        if let Some(clock) = &mut self.clock {
+            clock.stop(now);
+        }
+    }
+
+    // ends the game when the time of the camp to move is up; true if it was
+    pub fn check_flag(&mut self, now: u64) -> bool {
+        let camp = match self.clock.as_ref().and_then(|c| c.flagged(now)) {
+            Some(camp) => camp,
+            None => return false,
+        };
+        // the time is up even if it can not be logged; the flag falls again
+        // after a restart, as the clock has run all along
+        let _ = self.record(Entry::Flagged { camp, at: now });
+        self.flag(camp, now);
+        self.publish(GameEvent::State(Box::new(self.state())));
+        true
+    }
+
+    fn seat_mut(&mut self, camp: Camp) -> &mut Option<String> {
+        match camp {
+            Camp::White => &mut self.white,
+            Camp::Black => &mut self.black,
+        }
+    }
+
+    fn seat(&self, camp: Camp) -> &Option<String> {
+        match camp {
+            Camp::White => &self.white,
+            Camp::Black => &self.black,
+        }
+    }
+
+    fn is_free(&self, camp: Camp) -> bool {
+        self.seat(camp).is_none() && self.bot.map(|b| b.camp) != Some(camp)
+    }
+
+    // seats the user in the camp, or the first free one if none is asked for;
+    // without a free seat the user watches the game, and None is returned.
+    // A user already seated keeps its camp, one account never plays both
+    pub fn join(&mut self, user: &str, camp: Option<Camp>) -> io::Result<Option<Camp>> {
+        let seated = [Camp::White, Camp::Black]
+            .into_iter()
+            .find(|c| self.seat(*c).as_deref() == Some(user));
+        if seated.is_some() {
+            return Ok(seated);
+        }
+        let free = match camp {
+            Some(c) => Some(c).filter(|c| self.is_free(*c)),
+            None => [Camp::White, Camp::Black]
+                .into_iter()
+                .find(|c| self.is_free(*c)),
+        };
+        match free {
+            Some(c) => {
+                self.record(Entry::Seated {
+                    user: user.to_string(),
+                    camp: c,
+                })?;
+                *self.seat_mut(c) = Some(user.to_string());
+                self.spectators.retain(|s| s != user);
+            }
+            None => {
+                if !self.spectators.iter().any(|s| s == user) {
+                    self.spectators.push(user.to_string());
+                }
+            }
+        }
+        self.publish(GameEvent::Players {
+            white: self.white.clone(),
+            black: self.black.clone(),
+            spectators: self.spectators.clone(),
+        });
+        Ok(free)
+    }
+
+    // whether the user may send the cmd: moves and draw claims are for the
+    // player to move, a resignation for the player of the camp resigning
+    pub fn check_player(&self, user: &str, cmd: &Cmd) -> Result<(), String> {
+        let camp = match cmd {
+            Cmd::Resign(camp) => *camp,
+            _ => self.game.stage().turn(),
+        };
+        if self.bot.map(|b| b.camp) == Some(camp) {
+            return Err(format!("the bot plays {:?}", camp));
+        }
+        match self.seat(camp) {
+            Some(p) if p == user => Ok(()),
+            Some(p) => Err(format!("{} plays {:?}", p, camp)),
+            None => Err(format!("nobody plays {:?} yet", camp)),
+        }
+    }
+}
+
+// every game of the server, each one behind its own lock so that
+// a move in one game does not wait for the others
+#[derive(Default)]
+pub struct Rooms {
+    rooms: RwLock<HashMap<u64, Arc<Mutex<Room>>>>,
+    store: Option<Store>,
+}
+
+impl Rooms {
+    // the games logged in the store, replayed, and the new ones logged there
+    pub fn open(store: Store) -> io::Result<Rooms> {
+        let mut rooms = HashMap::new();
+        for (id, entries) in store.load()? {
+            let room = Room::replay(id, entries, store.log(id)?);
+            rooms.insert(id, Arc::new(Mutex::new(room)));
+        }
+        Ok(Rooms {
+            rooms: RwLock::new(rooms),
+            store: Some(store),
+        })
+    }
+
+    // a game the store can not log is not created
+    pub fn create(
+        &self,
+        bot: Option<BotConfig>,
+        clock: Option<TimeControl>,
+    ) -> io::Result<Arc<Mutex<Room>>> {
+        let mut rooms = self.rooms.write().unwrap();
+        let id = rooms.keys().max().map_or(1, |id| id + 1);
+        let log = match &self.store {
+            Some(store) => Some(store.log(id)?),
+            None => None,
+        };
+        let mut room = Room::new(id, bot, clock, log);
+        room.record(Entry::Created { bot, clock })?;
+        let room = Arc::new(Mutex::new(room));
+        rooms.insert(id, room.clone());
+        Ok(room)
+    }
+
+    pub fn get(&self, id: u64) -> Option<Arc<Mutex<Room>>> {
+        self.rooms.read().unwrap().get(&id).cloned()
+    }
+
+    // ends the games whose clock ran out; a panic in one game poisons its
+    // room, which is left alone from then on, but stops no other clock
+    pub fn check_flags(&self) {
+        let rooms: Vec<_> = self.rooms.read().unwrap().values().cloned().collect();
+        let now = now();
+        for room in rooms {
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+                if let Ok(mut room) = room.lock() {
+                    room.check_flag(now);
+                }
+            }));
+        }
+    }
+
+    // the games by id; a room poisoned by a panic is still listed as it was
+    pub fn list(&self) -> Vec<GameSummary> {
+        let rooms: Vec<_> = self.rooms.read().unwrap().values().cloned().collect();
+        let mut games: Vec<_> = rooms
+            .iter()
+            .map(|r| r.lock().unwrap_or_else(|e| e.into_inner()).summary())
+            .collect();
+        games.sort_by_key(|g| g.id);
+        games
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rooms;
+    use crate::store::Store;
+    use chess_core::{Camp, ChessError, Cmd, GameResult, ResultReason, TimeControl};
+    use server::{BotConfig, GameEvent};
+
+    #[test]
+    fn games_are_independent() {
+        let rooms = Rooms::default();
+        let first = rooms.create(None, None).unwrap();
+        let bot = Some(BotConfig {
+            camp: Camp::Black,
+            depth: 2,
+        });
+        let second = rooms.create(bot, None).unwrap();
+
+        // a game stays usable while another one is locked
+        let _locked = first.lock().unwrap();
+        let mut second = second.lock().unwrap();
+        let cmd = second.game.parse_san("e4").unwrap();
+        second.game.exec_cmd(&cmd).unwrap();
+        drop(second);
+        drop(_locked);
+
+        let games = rooms.list();
+        assert_eq!(games.iter().map(|g| g.id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(games[1].moves, 1);
+        assert_eq!(games[1].bot, bot);
+        assert_eq!(games[0].moves, 0);
+        assert!(rooms.get(3).is_none());
+
+        // nor does a game poisoned by a panic hide the others
+        let _ = std::thread::spawn(move || {
+            let _locked = first.lock().unwrap();
+            panic!("poisoned");
+        })
+        .join();
+        assert_eq!(rooms.list().len(), 2);
+    }
+
+    #[test]
+    fn players_keep_their_camp() {
+        let rooms = Rooms::default();
+        let room = rooms.create(None, None).unwrap();
+        let mut room = room.lock().unwrap();
+        assert_eq!(room.join("alice", None).unwrap(), Some(Camp::White));
+        assert_eq!(room.join("bob", Some(Camp::White)).unwrap(), None);
+        assert_eq!(room.join("bob", None).unwrap(), Some(Camp::Black));
+        assert_eq!(room.join("carol", None).unwrap(), None);
+        // joining again gives back the same seat
+        assert_eq!(room.join("alice", None).unwrap(), Some(Camp::White));
+        assert_eq!(
+            room.join("alice", Some(Camp::Black)).unwrap(),
+            Some(Camp::White)
+        );
+        assert_eq!(room.state().spectators, vec!["carol".to_string()]);
+
+        let e4 = room.game.parse_san("e4").unwrap();
+        assert!(room.check_player("bob", &e4).is_err());
+        assert!(room.check_player("carol", &e4).is_err());
+        assert!(room.check_player("alice", &e4).is_ok());
+        assert!(room
+            .check_player("alice", &Cmd::Resign(Camp::Black))
+            .is_err());
+        assert!(room.check_player("bob", &Cmd::Resign(Camp::Black)).is_ok());
+
+        // the camp of the bot is never free
+        let room = rooms
+            .create(
+                Some(BotConfig {
+                    camp: Camp::White,
+                    depth: 1,
+                }),
+                None,
+            )
+            .unwrap();
+        let mut room = room.lock().unwrap();
+        assert_eq!(room.join("alice", Some(Camp::White)).unwrap(), None);
+        assert_eq!(room.join("alice", None).unwrap(), Some(Camp::Black));
+        assert_eq!(room.join("alice", None).unwrap(), Some(Camp::Black));
+        assert!(room.check_player("alice", &e4).is_err());
+    }
+
+    #[test]
+    fn games_survive_a_restart() {
+        let dir = std::env::temp_dir().join(format!("games-{}", std::process::id()));
+        let dir = dir.to_str().unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+
+        let rooms = Rooms::open(Store::open(dir).unwrap()).unwrap();
+        let bot = Some(BotConfig {
+            camp: Camp::Black,
+            depth: 1,
+        });
+        rooms.create(None, None).unwrap();
+        let room = rooms.create(bot, None).unwrap();
+        {
+            let mut room = room.lock().unwrap();
+            room.join("alice", None).unwrap();
+            room.join("bob", None).unwrap();
+            for san in ["e4", "e5", "Nf3"] {
+                let cmd = room.game.parse_san(san).unwrap();
+                room.exec(&cmd).unwrap().unwrap();
+            }
+        }
+        drop(rooms);
+
+        // a crash in the middle of a write
+        let log = std::path::Path::new(dir).join("2.log");
+        let mut file = std::fs::OpenOptions::new().append(true).open(&log).unwrap();
+        std::io::Write::write_all(&mut file, b"{\"Played\":{\"cmd\":{\"Mo").unwrap();
+
+        let rooms = Rooms::open(Store::open(dir).unwrap()).unwrap();
+        assert_eq!(rooms.list().len(), 2);
+        let room = rooms.get(2).unwrap();
+        {
+            let mut room = room.lock().unwrap();
+            assert_eq!(room.bot, bot);
+            assert_eq!(room.white.as_deref(), Some("alice"));
+            assert!(room.spectators.is_empty());
+            assert_eq!(room.game.to_fen(), {
+                let mut game = chess_core::Game::new();
+                for san in ["e4", "e5", "Nf3"] {
+                    let cmd = game.parse_san(san).unwrap();
+                    game.exec_cmd(&cmd).unwrap();
+                }
+                game.to_fen()
+            });
+            let cmd = room.game.parse_san("Nc6").unwrap();
+            room.exec(&cmd).unwrap().unwrap();
+        }
+        assert_eq!(rooms.create(None, None).unwrap().lock().unwrap().id, 3);
+        drop(rooms);
+
+        let rooms = Rooms::open(Store::open(dir).unwrap()).unwrap();
+        assert_eq!(rooms.list()[1].moves, 4);
+        {
+            // after a failed write, nothing is played that the log misses
+            let room = rooms.get(2).unwrap();
+            let mut room = room.lock().unwrap();
+            room.log_broken = true;
+            let cmd = room.game.parse_san("Bc4").unwrap();
+            assert!(room.exec(&cmd).is_err());
+            assert!(room.join("carol", Some(Camp::Black)).is_ok());
+            assert_eq!(room.game.history().len(), 4);
+        }
+        drop(rooms);
+
+        // a bad line before others is not a crash, nothing is cut off
+        let mut file = std::fs::OpenOptions::new().append(true).open(&log).unwrap();
+        std::io::Write::write_all(&mut file, b"oops\n{\"Seated\":{}}\n").unwrap();
+        let len = std::fs::metadata(&log).unwrap().len();
+        assert!(Rooms::open(Store::open(dir).unwrap()).is_err());
+        assert_eq!(std::fs::metadata(&log).unwrap().len(), len);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn flags_fall_on_time() {
+        let dir = std::env::temp_dir().join(format!("clocks-{}", std::process::id()));
+        let dir = dir.to_str().unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+
+        let rooms = Rooms::open(Store::open(dir).unwrap()).unwrap();
+        let fischer = TimeControl::Fischer {
+            base: 1_000,
+            increment: 500,
+        };
+        let room = rooms.create(None, Some(fischer)).unwrap();
+        {
+            let mut room = room.lock().unwrap();
+            let e4 = room.game.parse_san("e4").unwrap();
+            room.exec_at(&e4, 10_000).unwrap().unwrap();
+            let e5 = room.game.parse_san("e5").unwrap();
+            room.exec_at(&e5, 10_800).unwrap().unwrap();
+            let state = room.clock_state(10_800).unwrap();
+            assert_eq!((state.white, state.black), (1_500, 700));
+            assert_eq!(state.running, Some(Camp::White));
+
+            assert!(!room.check_flag(12_299));
+            let nf3 = room.game.parse_san("Nf3").unwrap();
+            assert_eq!(
+                room.exec_at(&nf3, 12_300).unwrap(),
+                Err(ChessError::GameOver)
+            );
+            assert_eq!(
+                room.game.result(),
+                Some(GameResult::BlackWins(ResultReason::Timeout))
+            );
+        }
+        drop(rooms);
+
+        let rooms = Rooms::open(Store::open(dir).unwrap()).unwrap();
+        let room = rooms.get(1).unwrap();
+        let room = room.lock().unwrap();
+        assert_eq!(room.summary().clock, Some(fischer));
+        assert_eq!(
+            room.game.result(),
+            Some(GameResult::BlackWins(ResultReason::Timeout))
+        );
+        assert_eq!(room.clock.as_ref().unwrap().running(), None);
+        std::fs::remove_dir_all(dir).unwrap();
+
+        // a poisoned game does not stop the clocks of the others
+        let rooms = Rooms::default();
+        let games: Vec<_> = (0..2)
+            .map(|_| {
+                let room = rooms.create(None, Some(fischer)).unwrap();
+                let mut locked = room.lock().unwrap();
+                let e4 = locked.game.parse_san("e4").unwrap();
+                locked.exec_at(&e4, super::now() - 10_000).unwrap().unwrap();
+                drop(locked);
+                room
+            })
+            .collect();
+        let poisoned = games[0].clone();
+        let _ = std::thread::spawn(move || {
+            let _locked = poisoned.lock().unwrap();
+            panic!("poisoned");
+        })
+        .join();
+        rooms.check_flags();
+        assert_eq!(
+            games[1].lock().unwrap().game.result(),
+            Some(GameResult::WhiteWins(ResultReason::Timeout))
+        );
+    }
+
+    #[test]
+    fn subscribers_hear_the_changes() {
+        let rooms = Rooms::default();
+        let room = rooms.create(None, None).unwrap();
+        let mut room = room.lock().unwrap();
+        let mut events = room.subscribe();
+
+        room.join("alice", None).unwrap();
+        let e4 = room.game.parse_san("e4").unwrap();
+        room.exec(&e4).unwrap().unwrap();
+        assert!(room.exec(&e4).unwrap().is_err());
+
+        match events.try_recv().unwrap() {
+            GameEvent::Players { white, .. } => assert_eq!(white.as_deref(), Some("alice")),
+            e => panic!("{:?}", e),
+        }
+        match events.try_recv().unwrap() {
+            GameEvent::Cmd { ply, cmd, clock } => {
+                assert_eq!((ply, cmd), (0, e4));
+                assert!(clock.is_none());
+            }
+            e => panic!("{:?}", e),
+        }
+        // a refused cmd changes nothing
+        assert!(events.try_recv().is_err());
+    }
+}
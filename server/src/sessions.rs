@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use actix_web::error::ErrorUnauthorized;
+use actix_web::{Error, HttpRequest};
+use rand::Rng;
+
+// how long a token stays valid after the login
+const TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+// the users logged in, by token, with the time their token expires
+pub struct Sessions {
+    ttl: Duration,
+    users: RwLock<HashMap<String, (String, Instant)>>,
+}
+
+impl Default for Sessions {
+    fn default() -> Self {
+        Sessions {
+            ttl: TTL,
+            users: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+impl Sessions {
+    // a new random token for the user, whose password was checked
+    pub fn login(&self, user: &str) -> String {
+        let bytes: [u8; 32] = rand::thread_rng().gen();
+        let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        let expires = Instant::now() + self.ttl;
+        let mut users = self.users.write().unwrap();
+        users.retain(|_, (_, e)| *e > Instant::now());
+        users.insert(token.clone(), (user.to_string(), expires));
+        token
+    }
+
+    // the user of the "Authorization: Bearer <token>" header of the request
+    pub fn user_of(&self, req: &HttpRequest) -> Result<String, Error> {
+        let token = req
+            .headers()
+            .get("Authorization")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .ok_or_else(|| ErrorUnauthorized("log in first"))?;
+        let users = self.users.read().unwrap();
+        match users.get(token) {
+            Some((user, expires)) if *expires > Instant::now() => Ok(user.clone()),
+            Some(_) => Err(ErrorUnauthorized("session expired, log in again")),
+            None => Err(ErrorUnauthorized("unknown token, log in again")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sessions;
+    use actix_web::test::TestRequest;
+    use std::time::Duration;
+
+    fn user_of(sessions: &Sessions, token: &str) -> Result<String, String> {
+        let req = TestRequest::default()
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_http_request();
+        sessions.user_of(&req).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn tokens_are_random_and_expire() {
+        let sessions = Sessions::default();
+        let a = sessions.login("alice");
+        let b = sessions.login("alice");
+        assert_ne!(a, b);
+        assert_eq!(a.len(), 64);
+        assert_eq!(user_of(&sessions, &a), Ok("alice".to_string()));
+        assert!(user_of(&sessions, "alice-0").is_err());
+
+        let sessions = Sessions {
+            ttl: Duration::ZERO,
+            ..Sessions::default()
+        };
+        let token = sessions.login("bob");
+        assert_eq!(
+            user_of(&sessions, &token),
+            Err("session expired, log in again".to_string())
+        );
+    }
+}
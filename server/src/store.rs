@@ -0,0 +1,111 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use chess_core::{Camp, Cmd, TimeControl};
+use serde::{Deserialize, Serialize};
+
+use server::BotConfig;
+
+// a line of the log of a game, in the order it happened; at is the time
+// of the clocks, see rooms::now()
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Entry {
+    Created {
+        bot: Option<BotConfig>,
+        #[serde(default)]
+        clock: Option<TimeControl>,
+    },
+    Seated {
+        user: String,
+        camp: Camp,
+    },
+    Played {
+        cmd: Cmd,
+        at: u64,
+    },
+    Flagged {
+        camp: Camp,
+        at: u64,
+    },
+}
+
+// the games as a directory of append-only logs, one json entry a line in
+// <id>.log, so that they outlive the server
+pub struct Store {
+    dir: PathBuf,
+}
+
+// the open log of a game
+pub struct GameLog {
+    file: File,
+}
+
+impl GameLog {
+    // the entry is on the disk once this returns
+    pub fn append(&mut self, entry: &Entry) -> io::Result<()> {
+        let line = serde_json::to_string(entry)? + "\n";
+        self.file.write_all(line.as_bytes())?;
+        self.file.sync_data()
+    }
+}
+
+impl Store {
+    pub fn open(dir: &str) -> io::Result<Store> {
+        fs::create_dir_all(dir)?;
+        Ok(Store { dir: dir.into() })
+    }
+
+    fn path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{}.log", id))
+    }
+
+    pub fn log(&self, id: u64) -> io::Result<GameLog> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(id))?;
+        Ok(GameLog { file })
+    }
+
+    // the entries of every game by id; a line cut short by a crash can only
+    // be the last one of its log, and is dropped. Any other bad line is an
+    // error, the log is left as it is
+    pub fn load(&self) -> io::Result<Vec<(u64, Vec<Entry>)>> {
+        let mut games = Vec::new();
+        for file in fs::read_dir(&self.dir)? {
+            let path = file?.path();
+            let id = match path.extension().and_then(|e| e.to_str()) {
+                Some("log") => path
+                    .file_stem()
+                    .and_then(|s| s.to_str()?.parse::<u64>().ok()),
+                _ => None,
+            };
+            let Some(id) = id else { continue };
+
+            let mut entries = Vec::new();
+            let mut kept = 0;
+            for (n, line) in fs::read_to_string(&path)?.split_inclusive('\n').enumerate() {
+                if !line.ends_with('\n') {
+                    // cut it off, or the next entry would be glued to it
+                    println!("{}: dropping a broken last line", path.display());
+                    OpenOptions::new().write(true).open(&path)?.set_len(kept)?;
+                    break;
+                }
+                match serde_json::from_str(line) {
+                    Ok(entry) => entries.push(entry),
+                    Err(e) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("{} line {}: {}", path.display(), n + 1, e),
+                        ))
+                    }
+                }
+                kept += line.len() as u64;
+            }
+            games.push((id, entries));
+        }
+        games.sort_by_key(|(id, _)| *id);
+        Ok(games)
+    }
+}
@@ -0,0 +1,357 @@
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use db::{Token, DB};
+use server::{Colours, FieldError, Profile, ProfileUpdate, RegisterRequest, COLOURS};
+
+// the registered users, the salted hashes of their passwords and their
+// profiles, kept in a db file
+pub struct Users {
+    path: String,
+    db: Mutex<DB>,
+}
+
+// the fields added after the first version of the file, with their value
+// for the users registered before them; an empty name is the id
+fn profile_fields() -> [(&'static str, Token); 3] {
+    let colours = Colours::default();
+    [
+        ("name", Token::String(String::new())),
+        ("white", Token::Symbol(colours.white)),
+        ("black", Token::Symbol(colours.black)),
+    ]
+}
+
+// the db lexer reads a row key as a symbol, so an id is a letter then letters or digits
+fn check_id(id: &str) -> Result<(), FieldError> {
+    let mut chars = id.chars();
+    let first_is_letter = chars.next().is_some_and(|c| c.is_ascii_alphabetic());
+    if !first_is_letter || !chars.all(|c| c.is_ascii_alphanumeric()) || id.len() > 20 {
+        return Err(FieldError::new(
+            "id",
+            "an id is a letter then up to 19 letters or digits",
+        ));
+    }
+    // GET /users/me would not find it
+    if id == "me" {
+        return Err(FieldError::new("id", "me is not an id"));
+    }
+    Ok(())
+}
+
+fn check_password(field: &str, password: &str) -> Result<(), FieldError> {
+    if password.chars().count() < 6 {
+        return Err(FieldError::new(
+            field,
+            "a password has at least 6 characters",
+        ));
+    }
+    Ok(())
+}
+
+// names are quoted in the db file, so they cannot hold a quote
+fn check_display_name(name: &str) -> Result<(), FieldError> {
+    let len = name.chars().count();
+    if !(1..=30).contains(&len) || name.chars().any(|c| c == '\'' || c.is_control()) {
+        return Err(FieldError::new(
+            "display_name",
+            "a display name has 1 to 30 characters and no '",
+        ));
+    }
+    Ok(())
+}
+
+fn check_colours(colours: &Colours) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    for (field, colour) in [
+        ("colours.white", &colours.white),
+        ("colours.black", &colours.black),
+    ] {
+        if !COLOURS.contains(&colour.as_str()) {
+            let message = format!("the colours are {}", COLOURS.join(", "));
+            errors.push(FieldError::new(field, &message));
+        }
+    }
+    errors
+}
+
+fn hash(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .unwrap()
+        .to_string()
+}
+
+fn string_of(db: &mut DB, id: &str, field: &str) -> String {
+    match db.get(id, field) {
+        Ok(Token::String(s)) | Ok(Token::Symbol(s)) => s,
+        _ => String::new(),
+    }
+}
+
+fn profile_of(db: &mut DB, id: &str) -> Option<Profile> {
+    if !db.contains(id) {
+        return None;
+    }
+    let name = string_of(db, id, "name");
+    Some(Profile {
+        id: id.to_string(),
+        display_name: if name.is_empty() {
+            id.to_string()
+        } else {
+            name
+        },
+        colours: Colours {
+            white: string_of(db, id, "white"),
+            black: string_of(db, id, "black"),
+        },
+    })
+}
+
+impl Users {
+    // the users of the file, which is created when missing; a file that can
+    // not be read is an error, not an empty table written over the accounts
+    pub fn open(path: &str) -> io::Result<Users> {
+        let mut db = if Path::new(path).exists() {
+            DB::open(path).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} is not a table of users", path),
+                )
+            })?
+        } else {
+            DB::create(path, &["id", "password"])?
+        };
+        for (field, value) in profile_fields() {
+            if !db.has_field(field) {
+                db.add_field(field, value);
+            }
+        }
+        db.flush(path)?;
+        Ok(Users {
+            path: path.to_string(),
+            db: Mutex::new(db),
+        })
+    }
+
+    // the io error is a valid account that could not be saved, and was not
+    // registered
+    pub fn register(&self, req: &RegisterRequest) -> io::Result<Result<(), Vec<FieldError>>> {
+        let mut errors = Vec::new();
+        errors.extend(check_id(&req.id).err());
+        errors.extend(check_password("password", &req.password).err());
+        if let Some(name) = &req.display_name {
+            errors.extend(check_display_name(name).err());
+        }
+        if !errors.is_empty() {
+            return Ok(Err(errors));
+        }
+        let hash = hash(&req.password);
+
+        let mut db = self.db.lock().unwrap();
+        if db.contains(&req.id) {
+            return Ok(Err(vec![FieldError::new(
+                "id",
+                &format!("{} is taken", req.id),
+            )]));
+        }
+        // changed in memory once it is on the disk
+        let mut next = db.clone();
+        let mut row = vec![Token::Symbol(req.id.clone()), Token::String(hash)];
+        row.extend(profile_fields().into_iter().map(|(_, value)| value));
+        next.insert(&req.id, row);
+        if let Some(name) = &req.display_name {
+            next.set(&req.id, "name", Token::String(name.clone()))
+                .unwrap();
+        }
+        next.flush(&self.path)?;
+        *db = next;
+        Ok(Ok(()))
+    }
+
+    // whether the password is the one of the user
+    pub fn check(&self, id: &str, password: &str) -> bool {
+        let hash = match self.db.lock().unwrap().get(id, "password") {
+            Ok(Token::String(hash)) => hash,
+            _ => return false,
+        };
+        match PasswordHash::new(&hash) {
+            Ok(hash) => Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    pub fn profile(&self, id: &str) -> Option<Profile> {
+        profile_of(&mut self.db.lock().unwrap(), id)
+    }
+
+    // changes nothing unless every field of the update is valid and saved
+    pub fn update(
+        &self,
+        id: &str,
+        update: &ProfileUpdate,
+    ) -> io::Result<Result<Profile, Vec<FieldError>>> {
+        let mut errors = Vec::new();
+        if let Some(name) = &update.display_name {
+            errors.extend(check_display_name(name).err());
+        }
+        if let Some(colours) = &update.colours {
+            errors.extend(check_colours(colours));
+        }
+        if let Some(change) = &update.password {
+            if !self.check(id, &change.old) {
+                errors.push(FieldError::new("password.old", "wrong password"));
+            }
+            errors.extend(check_password("password.new", &change.new).err());
+        }
+        if !errors.is_empty() {
+            return Ok(Err(errors));
+        }
+        let hash = update.password.as_ref().map(|change| hash(&change.new));
+
+        let mut db = self.db.lock().unwrap();
+        if !db.contains(id) {
+            return Ok(Err(vec![FieldError::new("id", "no such user")]));
+        }
+        let mut next = db.clone();
+        let mut set = |field: &str, value: Token| next.set(id, field, value).unwrap();
+        if let Some(name) = &update.display_name {
+            set("name", Token::String(name.clone()));
+        }
+        if let Some(colours) = &update.colours {
+            set("white", Token::Symbol(colours.white.clone()));
+            set("black", Token::Symbol(colours.black.clone()));
+        }
+        if let Some(hash) = hash {
+            set("password", Token::String(hash));
+        }
+        next.flush(&self.path)?;
+        *db = next;
+        Ok(Ok(profile_of(&mut db, id).unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Users;
+    use server::{Colours, PasswordChange, ProfileUpdate, RegisterRequest};
+
+    fn temp_db(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}-{}.db", name, std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn register(users: &Users, id: &str, password: &str) -> Result<(), Vec<String>> {
+        let req = RegisterRequest {
+            id: id.to_string(),
+            password: password.to_string(),
+            display_name: None,
+        };
+        users
+            .register(&req)
+            .unwrap()
+            .map_err(|errors| errors.into_iter().map(|e| e.field).collect())
+    }
+
+    #[test]
+    fn passwords_are_checked_and_kept() {
+        let path = temp_db("users");
+        let users = Users::open(&path).unwrap();
+        assert!(register(&users, "alice", "secret1").is_ok());
+        assert_eq!(
+            register(&users, "alice", "secret2"),
+            Err(vec!["id".to_string()])
+        );
+        assert_eq!(
+            register(&users, "1alice", "short"),
+            Err(vec!["id".to_string(), "password".to_string()])
+        );
+        assert!(register(&users, "me", "secret1").is_err());
+        assert!(users.check("alice", "secret1"));
+        assert!(!users.check("alice", "secret2"));
+        assert!(!users.check("bob", "secret1"));
+
+        // the hash is salted, not the password, and read back from the file
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("secret1"));
+        assert!(Users::open(&path).unwrap().check("alice", "secret1"));
+
+        // an account that can not be saved is not registered
+        let dir = std::env::temp_dir().join(format!("users-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let gone = Users::open(dir.join("users.db").to_str().unwrap()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let req = RegisterRequest {
+            id: "carol".to_string(),
+            password: "secret1".to_string(),
+            display_name: None,
+        };
+        assert!(gone.register(&req).is_err());
+        assert!(!gone.check("carol", "secret1"));
+
+        // a file that is not a table is left alone
+        std::fs::write(&path, "'id' 'password'\nalice 'x'\n").unwrap();
+        assert!(Users::open(&path).is_err());
+        assert!(std::fs::read_to_string(&path).unwrap().contains("alice"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn profiles_change_only_when_valid() {
+        let path = temp_db("profiles");
+        // a file of the first version, without profiles
+        std::fs::write(&path, "id password \nalice 'nohash' \n").unwrap();
+        let users = Users::open(&path).unwrap();
+        let alice = users.profile("alice").unwrap();
+        assert_eq!(alice.display_name, "alice");
+        assert_eq!(alice.colours, Colours::default());
+        assert!(users.profile("bob").is_none());
+
+        register(&users, "bob", "secret1").unwrap();
+        let update = ProfileUpdate {
+            display_name: Some("Bobby".to_string()),
+            colours: Some(Colours {
+                white: "blue".to_string(),
+                black: "pink".to_string(),
+            }),
+            password: Some(PasswordChange {
+                old: "wrong1".to_string(),
+                new: "secret2".to_string(),
+            }),
+        };
+        let fields: Vec<String> = users
+            .update("bob", &update)
+            .unwrap()
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.field)
+            .collect();
+        assert_eq!(fields, vec!["colours.black", "password.old"]);
+        assert_eq!(users.profile("bob").unwrap().display_name, "bob");
+
+        let update = ProfileUpdate {
+            colours: Some(Colours {
+                white: "blue".to_string(),
+                black: "yellow".to_string(),
+            }),
+            password: Some(PasswordChange {
+                old: "secret1".to_string(),
+                new: "secret2".to_string(),
+            }),
+            ..update
+        };
+        let bob = users.update("bob", &update).unwrap().unwrap();
+        assert_eq!(bob.display_name, "Bobby");
+        assert_eq!(Users::open(&path).unwrap().profile("bob").unwrap(), bob);
+        assert!(users.check("bob", "secret2"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -211,6 +211,8 @@ pub struct Areas {
     pub input_area: InputArea,
     pub grid_area: GridArea,
     pub message: String,
+    // lines shown at the right of the grid, a form or a list
+    pub panel: Vec<String>,
 }
 
 pub struct Ui {
@@ -240,6 +242,7 @@ impl Ui {
                 },
 
                 message: String::new(),
+                panel: Vec::new(),
             },
             stdout: stdout().into_raw_mode().unwrap(),
             stdin_channel: Self::spawn_stdin_channel(),
@@ -264,6 +267,10 @@ impl Ui {
             i += 1;
         }
 
+        for (i, l) in self.areas.panel.iter().enumerate() {
+            write!(self.stdout, "{}{}", termion::cursor::Goto(42, 6 + i as u16), l,).unwrap();
+        }
+
         let mut i = 0;
         for l in self.areas.input_area.render().lines.iter() {
             write!(self.stdout, "{}{}", termion::cursor::Goto(1, 3 + i), l,).unwrap();
@@ -367,6 +374,22 @@ impl Ui {
         format!("{}{}{}", color::Fg(color::Red), s, color::Fg(color::White))
     }
 
+    // s in the colour of the name, "black", "red", "green", "yellow", "blue",
+    // "magenta", "cyan" or "white"
+    pub fn named_color(s: &str, name: &str) -> String {
+        let fg = match name {
+            "black" => color::Fg(color::Black).to_string(),
+            "red" => color::Fg(color::Red).to_string(),
+            "green" => color::Fg(color::Green).to_string(),
+            "yellow" => color::Fg(color::Yellow).to_string(),
+            "blue" => color::Fg(color::Blue).to_string(),
+            "magenta" => color::Fg(color::Magenta).to_string(),
+            "cyan" => color::Fg(color::Cyan).to_string(),
+            _ => color::Fg(color::White).to_string(),
+        };
+        format!("{}{}{}", fg, s, color::Fg(color::White))
+    }
+
     //pub async fn run(&mut self) {
     //    let mut stdout = stdout().into_raw_mode().unwrap();
     //    stdout.flush().unwrap();